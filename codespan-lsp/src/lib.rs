@@ -14,7 +14,7 @@ use codespan::{
     ByteIndex, ByteIndexError, ByteOffset, CodeMap, ColumnIndex, FileMap, FileName, LineIndex,
     LineIndexError, LocationError, RawIndex, RawOffset, Span,
 };
-use codespan_reporting::{Diagnostic, Severity};
+use codespan_reporting::{Diagnostic, Label, Note, Severity};
 use languageserver_types as lsp;
 use url::Url;
 
@@ -180,6 +180,9 @@ const UNKNOWN_RANGE: lsp::Range = lsp::Range {
 /// Since the language client requires `Url`s to locate the errors `codespan_name_to_file` is
 /// necessary to resolve codespan `FileName`s
 ///
+/// The labels of child diagnostics, and of their own children in turn, are added to the related
+/// information, while notes are appended to the message.
+///
 /// `code` and `source` are left empty by this function
 pub fn make_lsp_diagnostic<F>(
     code_map: &CodeMap,
@@ -213,36 +216,67 @@ where
         }
     };
 
-    let related_information = diagnostic
-        .labels
-        .into_iter()
-        .map(|label| {
-            let (file_map, range) = match primary_file_map {
-                // If the label's span does not point anywhere, assume it comes from the same file
-                // as the primary label
-                Some(file_map) if label.span.start() == ByteIndex::none() => {
-                    (file_map, UNKNOWN_RANGE)
-                },
-                Some(_) | None => {
-                    let file_map = find_file(label.span.start())?;
-                    let range = byte_span_to_range(file_map, label.span)?;
+    let mut make_related_information = |label: &Label, message: String| {
+        let (file_map, range) = match primary_file_map {
+            // If the label's span does not point anywhere, assume it comes from the same file
+            // as the primary label
+            Some(file_map) if label.span.start() == ByteIndex::none() => {
+                (file_map, UNKNOWN_RANGE)
+            },
+            Some(_) | None => {
+                let file_map = find_file(label.span.start())?;
+                let range = byte_span_to_range(file_map, label.span)?;
 
-                    (file_map, range)
-                },
-            };
+                (file_map, range)
+            },
+        };
 
-            let uri = codespan_name_to_file(file_map.name())
-                .map_err(|()| Error::UnableToCorrelateFilename(file_map.name().clone()))?;
+        let uri = codespan_name_to_file(file_map.name())
+            .map_err(|()| Error::UnableToCorrelateFilename(file_map.name().clone()))?;
 
-            Ok(lsp::DiagnosticRelatedInformation {
-                location: lsp::Location { uri, range },
-                message: label.message.unwrap_or(String::new()),
-            })
+        Ok(lsp::DiagnosticRelatedInformation {
+            location: lsp::Location { uri, range },
+            message,
+        })
+    };
+
+    let mut related_information = diagnostic
+        .labels
+        .iter()
+        .map(|label| {
+            let message = label.message.clone().unwrap_or_default();
+            make_related_information(label, message)
         })
         .collect::<Result<Vec<_>, Error>>()?;
 
+    // The LSP has no notion of sub-diagnostics, so the labels of child
+    // diagnostics are added to the related information, and any notes are
+    // appended to the main message. Nested children are flattened in order,
+    // each following its parent.
+    let mut message = diagnostic.message;
+    let mut notes = diagnostic.notes;
+    let mut children = diagnostic.children;
+    children.reverse();
+    while let Some(child) = children.pop() {
+        for label in &child.labels {
+            let message = match label.message {
+                Some(ref label_message) => format!("{}: {}", child.message, label_message),
+                None => child.message.clone(),
+            };
+            related_information.push(make_related_information(label, message)?);
+        }
+        if child.labels.is_empty() {
+            notes.push(Note::new(child.severity, child.message));
+        }
+        notes.extend(child.notes);
+        children.extend(child.children.into_iter().rev());
+    }
+    for note in notes {
+        message.push_str(&format!("\n{}: {}", note.severity, note.message));
+    }
+
     Ok(lsp::Diagnostic {
-        message: diagnostic.message,
+        message,
         range: primary_label_range,
         severity: Some(make_lsp_severity(diagnostic.severity)),
         related_information: if related_information.is_empty() {
@@ -325,4 +359,93 @@ te
             })
         );
     }
+
+    #[test]
+    fn diagnostic_children_and_notes() {
+        let mut code_map = CodeMap::new();
+        let file_map = code_map.add_filemap("test".into(), "let x = 1\nlet x = 2\n".into());
+        let span = |line: RawIndex| {
+            let start = file_map.byte_index(line.into(), 4.into()).unwrap();
+            Span::from_offset(start, 1.into())
+        };
+        let diagnostic = Diagnostic::new_error("`x` is defined more than once")
+            .with_label(Label::new_primary(span(1)).with_message("redefined here"))
+            .with_help("try renaming one of the definitions")
+            .with_child(
+                Diagnostic::new_note("previous definition here")
+                    .with_label(Label::new_secondary(span(0))),
+            );
+
+        let url = Url::parse("file:///test").unwrap();
+        let result = make_lsp_diagnostic(&code_map, diagnostic, |_| Ok(url.clone())).unwrap();
+
+        assert_eq!(
+            result.message,
+            "`x` is defined more than once\nhelp: try renaming one of the definitions",
+        );
+        assert_eq!(
+            result.related_information,
+            Some(vec![
+                lsp::DiagnosticRelatedInformation {
+                    location: lsp::Location {
+                        uri: url.clone(),
+                        range: byte_span_to_range(&file_map, span(1)).unwrap(),
+                    },
+                    message: "redefined here".to_owned(),
+                },
+                lsp::DiagnosticRelatedInformation {
+                    location: lsp::Location {
+                        uri: url.clone(),
+                        range: byte_span_to_range(&file_map, span(0)).unwrap(),
+                    },
+                    message: "previous definition here".to_owned(),
+                },
+            ]),
+        );
+    }
+
+    #[test]
+    fn nested_children() {
+        let mut code_map = CodeMap::new();
+        let file_map = code_map.add_filemap("test".into(), "a\nb\nc\nd\n".into());
+        let span = |line: RawIndex| {
+            let start = file_map.byte_index(line.into(), 0.into()).unwrap();
+            Span::from_offset(start, 1.into())
+        };
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_label(Label::new_primary(span(0)))
+            .with_child(
+                Diagnostic::new_note("child")
+                    .with_label(Label::new_secondary(span(1)))
+                    .with_child(
+                        Diagnostic::new_note("grandchild")
+                            .with_label(Label::new_secondary(span(2)))
+                            .with_note("grandchild note"),
+                    ),
+            )
+            .with_child(
+                Diagnostic::new_note("second child").with_label(Label::new_secondary(span(3))),
+            );
+
+        let url = Url::parse("file:///test").unwrap();
+        let result = make_lsp_diagnostic(&code_map, diagnostic, |_| Ok(url.clone())).unwrap();
+
+        assert_eq!(result.message, "oops\nnote: grandchild note");
+        let related_information = result
+            .related_information
+            .unwrap()
+            .into_iter()
+            .map(|info| (info.location.range, info.message))
+            .collect::<Vec<_>>();
+        let range = |line| byte_span_to_range(&file_map, span(line)).unwrap();
+        assert_eq!(
+            related_information,
+            vec![
+                (range(0), String::new()),
+                (range(1), "child".to_owned()),
+                (range(2), "grandchild".to_owned()),
+                (range(3), "second child".to_owned()),
+            ],
+        );
+    }
 }
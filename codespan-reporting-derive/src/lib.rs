@@ -0,0 +1,392 @@
+//! A derive macro for declaring diagnostics as structs
+//!
+//! Rather than building diagnostics up in the middle of a type checker, each
+//! kind of diagnostic can be declared as a struct, with its message, labels
+//! and notes written as attributes:
+//!
+//! ```rust
+//! extern crate codespan;
+//! extern crate codespan_reporting;
+//! #[macro_use]
+//! extern crate codespan_reporting_derive;
+//!
+//! use codespan::ByteSpan;
+//! use codespan_reporting::{Diagnostic, IntoDiagnostic, Severity};
+//!
+//! #[derive(IntoDiagnostic)]
+//! #[error("mismatched types")]
+//! #[code = "E0308"]
+//! #[help("try converting the `{found}` to an `{expected}`")]
+//! struct MismatchedTypes {
+//!     #[primary_label("expected `{expected}`, found `{found}`")]
+//!     span: ByteSpan,
+//!     #[secondary_label("expected due to this")]
+//!     annotation: Option<ByteSpan>,
+//!     #[note]
+//!     reason: Option<String>,
+//!     expected: String,
+//!     found: String,
+//! }
+//!
+//! # fn main() {
+//! let diagnostic = MismatchedTypes {
+//!     span: ByteSpan::default(),
+//!     annotation: None,
+//!     reason: None,
+//!     expected: "Int".to_owned(),
+//!     found: "String".to_owned(),
+//! }.into_diagnostic();
+//!
+//! assert_eq!(diagnostic.severity, Severity::Error);
+//! assert_eq!(diagnostic.code, Some("E0308".to_owned()));
+//! assert_eq!(
+//!     diagnostic.labels[0].message,
+//!     Some("expected `Int`, found `String`".to_owned()),
+//! );
+//! # }
+//! ```
+//!
+//! The struct is given exactly one of `#[error("...")]`, `#[warning("...")]`
+//! or `#[bug("...")]`, which sets the severity and message of the
+//! diagnostic. It may also have a `#[code = "..."]`, and any number of
+//! `#[note("...")]` and `#[help("...")]` notes.
+//!
+//! Fields can be marked with:
+//!
+//! | Attribute                                          | Field type                       |
+//! |----------------------------------------------------|----------------------------------|
+//! | `#[primary_label]`, `#[primary_label("...")]`      | `ByteSpan` or `Option<ByteSpan>` |
+//! | `#[secondary_label]`, `#[secondary_label("...")]`  | `ByteSpan` or `Option<ByteSpan>` |
+//! | `#[note]`, `#[help]`                               | `T` or `Option<T>`, where `T: Display` |
+//!
+//! Messages are format strings, which can refer to the fields of the struct
+//! by name, as in `{expected}` or `{span:?}`. Each name is checked when the
+//! diagnostic is derived:
+//!
+//! ```rust,compile_fail
+//! # extern crate codespan;
+//! # #[macro_use]
+//! # extern crate codespan_reporting_derive;
+//! # use codespan::ByteSpan;
+//! #[derive(IntoDiagnostic)]
+//! #[error("unknown variable `{nmae}`")]
+//! struct UnknownVariable {
+//!     #[primary_label]
+//!     span: ByteSpan,
+//!     name: String,
+//! }
+//! # fn main() {}
+//! ```
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use syn::spanned::Spanned;
+use syn::{Attribute, Data, DeriveInput, Expr, ExprLit, Fields, Ident, Lit, LitStr, Meta, Type};
+
+#[proc_macro_derive(
+    IntoDiagnostic,
+    attributes(bug, error, warning, code, note, help, primary_label, secondary_label)
+)]
+pub fn derive_into_diagnostic(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    match into_diagnostic(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => compile_errors(error).into(),
+    }
+}
+
+/// Report an error at the location it points to. This is written out rather
+/// than using `syn::Error::to_compile_error`, which refers to `::core`, and
+/// so doesn't work in crates using the 2015 edition.
+fn compile_errors(error: syn::Error) -> TokenStream2 {
+    error
+        .into_iter()
+        .map(|error| {
+            let message = error.to_string();
+            quote_spanned!(error.span()=> compile_error!(#message);)
+        })
+        .collect()
+}
+
+/// A message given as a format string, along with the fields that it refers
+/// to
+struct Message {
+    format: LitStr,
+    args: Vec<Ident>,
+}
+
+impl Message {
+    fn parse(attr: &Attribute, fields: &[Ident]) -> syn::Result<Message> {
+        let format = attr.parse_args::<LitStr>()?;
+        let args = format_args(&format, fields)?;
+        Ok(Message { format, args })
+    }
+
+    fn to_tokens(&self) -> TokenStream2 {
+        let format = &self.format;
+        let args = &self.args;
+        quote!(format!(#format, #(#args = #args),*))
+    }
+}
+
+/// The names of the fields referred to by a format string, checking that
+/// each of them is one of `fields`
+fn format_args(format: &LitStr, fields: &[Ident]) -> syn::Result<Vec<Ident>> {
+    let src = format.value();
+    let mut args: Vec<Ident> = Vec::new();
+    let mut chars = src.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+            },
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+            },
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => placeholder.push(ch),
+                        None => return Err(syn::Error::new(format.span(), "unterminated `{`")),
+                    }
+                }
+
+                let name = placeholder.split(':').next().unwrap_or("").trim();
+                let field = fields.iter().find(|field| *field == name);
+                match field {
+                    Some(field) => {
+                        if !args.contains(field) {
+                            args.push(field.clone());
+                        }
+                    },
+                    None if name.is_empty() || name.starts_with(|ch: char| ch.is_ascii_digit()) => {
+                        return Err(syn::Error::new(
+                            format.span(),
+                            "format arguments must be referred to by the name of a field",
+                        ));
+                    },
+                    None => {
+                        return Err(syn::Error::new(
+                            format.span(),
+                            format!("no field named `{}` to use in this message", name),
+                        ));
+                    },
+                }
+            },
+            '}' => return Err(syn::Error::new(format.span(), "unmatched `}`")),
+            _ => {},
+        }
+    }
+
+    Ok(args)
+}
+
+/// Whether a field has a type of the form `Option<T>`
+fn is_option(ty: &Type) -> bool {
+    match *ty {
+        Type::Path(ref path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+/// An identifier for a variable used in the generated code, which can't
+/// clash with the names of fields
+fn local(name: &str) -> Ident {
+    Ident::new(name, Span::mixed_site())
+}
+
+/// Wrap `tokens` so that they are only run if the field has a value, binding
+/// the value to `value`
+fn for_value(field: &Ident, ty: &Type, tokens: TokenStream2) -> TokenStream2 {
+    let value = local("value");
+    if is_option(ty) {
+        quote!(if let Some(#value) = #field { #tokens })
+    } else {
+        quote!({ let #value = #field; #tokens })
+    }
+}
+
+fn into_diagnostic(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let diagnostic = local("diagnostic");
+    let value = local("value");
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    "`IntoDiagnostic` can only be derived for structs with named fields",
+                ))
+            },
+        },
+        _ => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "`IntoDiagnostic` can only be derived for structs",
+            ))
+        },
+    };
+    let field_names = fields
+        .iter()
+        .filter_map(|field| field.ident.clone())
+        .collect::<Vec<_>>();
+
+    let mut header = None;
+    let mut code = None;
+    let mut notes = Vec::new();
+    let mut used_fields: Vec<Ident> = Vec::new();
+
+    for attr in &input.attrs {
+        let severity = match attr.path().get_ident() {
+            Some(ident) if ident == "bug" => quote!(Bug),
+            Some(ident) if ident == "error" => quote!(Error),
+            Some(ident) if ident == "warning" => quote!(Warning),
+            Some(ident) if ident == "code" => {
+                code = Some(match attr.meta {
+                    Meta::NameValue(ref meta) => match meta.value {
+                        Expr::Lit(ExprLit {
+                            lit: Lit::Str(ref code),
+                            ..
+                        }) => code.clone(),
+                        _ => return Err(syn::Error::new(meta.value.span(), "expected a string")),
+                    },
+                    _ => return Err(syn::Error::new(attr.span(), "expected `#[code = \"...\"]`")),
+                });
+                continue;
+            },
+            Some(ident) if ident == "note" || ident == "help" => {
+                let message = Message::parse(attr, &field_names)?;
+                used_fields.extend(message.args.iter().cloned());
+                let message = message.to_tokens();
+                notes.push(match ident.to_string().as_str() {
+                    "note" => quote!(#diagnostic = #diagnostic.with_note(#message);),
+                    _ => quote!(#diagnostic = #diagnostic.with_help(#message);),
+                });
+                continue;
+            },
+            _ => continue,
+        };
+
+        if header.is_some() {
+            return Err(syn::Error::new(
+                attr.span(),
+                "a diagnostic can only have one of `#[bug]`, `#[error]` or `#[warning]`",
+            ));
+        }
+        let message = Message::parse(attr, &field_names)?;
+        used_fields.extend(message.args.iter().cloned());
+        header = Some((severity, message.to_tokens()));
+    }
+
+    let (severity, message) = header.ok_or_else(|| {
+        syn::Error::new(
+            Span::call_site(),
+            "expected a `#[bug(\"...\")]`, `#[error(\"...\")]` or `#[warning(\"...\")]` attribute",
+        )
+    })?;
+
+    let mut labels = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().expect("named field");
+
+        for attr in &field.attrs {
+            let path = attr.path();
+            let style = if path.is_ident("primary_label") {
+                quote!(new_primary)
+            } else if path.is_ident("secondary_label") {
+                quote!(new_secondary)
+            } else if path.is_ident("note") || path.is_ident("help") {
+                if let Meta::List(_) = attr.meta {
+                    return Err(syn::Error::new(
+                        attr.span(),
+                        "the value of the field is used as the note, so it can't be given a \
+                         message",
+                    ));
+                }
+                let method = if path.is_ident("note") {
+                    quote!(with_note)
+                } else {
+                    quote!(with_help)
+                };
+                let note = quote! {
+                    #diagnostic = #diagnostic.#method(::std::string::ToString::to_string(#value));
+                };
+                notes.push(for_value(ident, &field.ty, note));
+                used_fields.push(ident.clone());
+                continue;
+            } else {
+                continue;
+            };
+
+            let label = match attr.meta {
+                Meta::Path(_) => quote! {
+                    ::codespan_reporting::Label::#style(::std::clone::Clone::clone(#value))
+                },
+                _ => {
+                    let message = Message::parse(attr, &field_names)?;
+                    used_fields.extend(message.args.iter().cloned());
+                    let message = message.to_tokens();
+                    quote! {
+                        ::codespan_reporting::Label::#style(::std::clone::Clone::clone(#value))
+                            .with_message(#message)
+                    }
+                },
+            };
+            labels.push(for_value(
+                ident,
+                &field.ty,
+                quote!(#diagnostic = #diagnostic.with_label(#label);),
+            ));
+            used_fields.push(ident.clone());
+        }
+    }
+
+    // Only bind the fields that are used, to avoid unused variable warnings
+    let bindings = field_names
+        .iter()
+        .filter(|field| used_fields.contains(field))
+        .collect::<Vec<_>>();
+    let code = code.map(|code| quote!(#diagnostic = #diagnostic.with_code(#code);));
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::codespan_reporting::IntoDiagnostic for #name #ty_generics
+            #where_clause
+        {
+            #[allow(unused_mut)]
+            fn into_diagnostic(self) -> ::codespan_reporting::Diagnostic {
+                let #name { #(ref #bindings,)* .. } = self;
+                let mut #diagnostic = ::codespan_reporting::Diagnostic::new(
+                    ::codespan_reporting::Severity::#severity,
+                    #message,
+                );
+                #code
+                #(#labels)*
+                #(#notes)*
+                #diagnostic
+            }
+        }
+
+        impl #impl_generics ::std::convert::From<#name #ty_generics>
+            for ::codespan_reporting::Diagnostic #where_clause
+        {
+            fn from(value: #name #ty_generics) -> ::codespan_reporting::Diagnostic {
+                ::codespan_reporting::IntoDiagnostic::into_diagnostic(value)
+            }
+        }
+    })
+}
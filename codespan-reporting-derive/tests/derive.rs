@@ -0,0 +1,98 @@
+extern crate codespan;
+extern crate codespan_reporting;
+#[macro_use]
+extern crate codespan_reporting_derive;
+
+use codespan::{ByteSpan, CodeMap, Span};
+use codespan_reporting::termcolor::NoColor;
+use codespan_reporting::{emit, Config, Diagnostic, IntoDiagnostic, Severity};
+use std::fmt;
+
+#[derive(IntoDiagnostic)]
+#[error("mismatched types")]
+#[code = "E0308"]
+#[help("try converting the `{{{found}}}` to an `{expected}`")]
+struct MismatchedTypes {
+    #[primary_label("expected `{expected}`, found `{found}`")]
+    span: ByteSpan,
+    #[secondary_label("expected due to this")]
+    annotation: Option<ByteSpan>,
+    #[note]
+    reason: Option<String>,
+    expected: String,
+    found: String,
+}
+
+#[test]
+fn derived_diagnostic() {
+    let mut codemap = CodeMap::new();
+    let file = codemap.add_filemap("test".into(), "let x: Int = \"\";\n".to_string());
+    let span = |start: u32, len: i64| {
+        let start = file.byte_index(0.into(), start.into()).unwrap();
+        Span::from_offset(start, len.into())
+    };
+
+    let diagnostic = Diagnostic::from(MismatchedTypes {
+        span: span(13, 2),
+        annotation: Some(span(7, 3)),
+        reason: None,
+        expected: "Int".to_owned(),
+        found: "String".to_owned(),
+    });
+
+    let mut writer = NoColor::new(Vec::new());
+    emit(&mut writer, &Config::default(), &codemap, &diagnostic).unwrap();
+    assert_eq!(
+        String::from_utf8(writer.into_inner()).unwrap(),
+        "\
+error[E0308]: mismatched types
+- <test>:1:14
+1 | let x: Int = \"\";
+  |        ---   ^^ expected `Int`, found `String`
+  |        |
+  |        expected due to this
+  = help: try converting the `{String}` to an `Int`
+",
+    );
+}
+
+/// Fields can share names with the variables used by the generated code,
+/// and the struct can be generic
+#[derive(IntoDiagnostic)]
+#[warning("unused {value}")]
+#[note("{diagnostic}")]
+struct Unused<T: fmt::Display> {
+    #[primary_label]
+    value: ByteSpan,
+    #[help]
+    diagnostic: T,
+    #[note]
+    ignored: Option<u32>,
+}
+
+#[test]
+fn hygiene_and_generics() {
+    let diagnostic = Unused {
+        value: ByteSpan::default(),
+        diagnostic: "remove it",
+        ignored: Some(1),
+    }
+    .into_diagnostic();
+
+    assert_eq!(diagnostic.severity, Severity::Warning);
+    assert_eq!(diagnostic.message, "unused 0..0");
+    assert_eq!(diagnostic.labels.len(), 1);
+    let notes = diagnostic
+        .notes
+        .iter()
+        .map(|note| (note.severity, note.message.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        notes,
+        vec![
+            (Severity::Note, "remove it"),
+            (Severity::Help, "remove it"),
+            (Severity::Note, "1"),
+        ],
+    );
+}
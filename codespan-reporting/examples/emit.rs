@@ -3,11 +3,14 @@ extern crate codespan_reporting;
 #[macro_use]
 extern crate structopt;
 
+use std::io;
 use structopt::StructOpt;
 
 use codespan::{CodeMap, Span};
 use codespan_reporting::termcolor::StandardStream;
-use codespan_reporting::{emit, ColorArg, Diagnostic, Label, Severity};
+use codespan_reporting::{emit, emit_explanation, emit_html, Applicability, ColorArg, Config,
+                         Diagnostic, DisplayStyle, Glyphs, Label, Registry, Severity, Styles,
+                         Suggestion, HTML_STYLESHEET};
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "emit")]
@@ -20,10 +23,58 @@ pub struct Opts {
         raw(possible_values = "ColorArg::VARIANTS", case_insensitive = "true")
     )]
     pub color: ColorArg,
+    /// The number of lines of context to show around each label
+    #[structopt(long = "context", default_value = "0")]
+    pub context_lines: usize,
+    /// Show one line per label, rather than annotated source code
+    #[structopt(long = "short")]
+    pub short: bool,
+    /// Override the colors of the output, as in
+    /// `header-warning=01;35:primary-label-warning=35`
+    #[structopt(long = "styles", parse(try_from_str), default_value = "")]
+    pub styles: Styles,
+    /// Draw snippets with Unicode box-drawing characters
+    #[structopt(long = "unicode")]
+    pub unicode: bool,
+    /// Trim source lines to fit within this many columns, defaulting to the
+    /// width of the terminal
+    #[structopt(long = "max-width")]
+    pub max_width: Option<usize>,
+    /// Print the explanation of a diagnostic code, rather than the diagnostics
+    #[structopt(long = "explain")]
+    pub explain: Option<String>,
+    /// Print the diagnostics to stdout as an HTML document
+    #[structopt(long = "html")]
+    pub html: bool,
 }
 
+const E0001: &str = "\
+# Unexpected type in application
+
+A function was applied to an argument of the wrong type, for example:
+
+```
+(+ test \"\")
+```
+
+The arguments of `+` must be **integers**.
+";
+
 fn main() {
     let opts = Opts::from_args();
+
+    let registry = Registry::new().with_explanation("E0001", E0001);
+    if let Some(code) = opts.explain {
+        match registry.explanation(&code) {
+            Some(explanation) => {
+                let writer = StandardStream::stdout(opts.color.into());
+                emit_explanation(&mut writer.lock(), &opts.styles, explanation).unwrap();
+            },
+            None => eprintln!("no explanation for `{}`", code),
+        }
+        return;
+    }
+
     let mut code_map = CodeMap::new();
 
     let source = r##"
@@ -43,6 +94,13 @@ fn main() {
             Label::new_secondary(Span::from_offset(str_start, 2.into()))
                 .with_message("Expected integer but got string"),
         )
+        .with_note("expected type `Int`\n   found type `String`")
+        .with_suggestion(Suggestion::new(
+            Span::from_offset(str_start, 2.into()),
+            "try using an integer literal",
+            "0",
+            Applicability::MaybeIncorrect,
+        ))
         .with_code("E0001");
 
     let line_start = file_map.byte_index(2.into(), 0.into()).unwrap();
@@ -51,11 +109,57 @@ fn main() {
         "`+` function has no effect unless its result is used",
     ).with_label(Label::new_primary(Span::from_offset(line_start, 11.into())));
 
-    let diagnostics = [error, warning];
+    let fizz_buzz_source = r##"
+(define (fizz-buzz num)
+  (match num
+    ((and (= (% num 5) 0) (= (% num 3) 0)) "FizzBuzz")
+    ((= (% num 3) 0) "Fizz")
+    ((= (% num 5) 0) "Buzz")
+    (_ num)))
+"##;
+    let fizz_buzz_map =
+        code_map.add_filemap("FizzBuzz.fun".into(), fizz_buzz_source.to_string());
+
+    let match_start = fizz_buzz_map.byte_index(2.into(), 2.into()).unwrap();
+    let match_end = fizz_buzz_map.byte_index(6.into(), 12.into()).unwrap();
+    let match_error = Diagnostic::new_error("`match` arms have incompatible types")
+        .with_code("E0308")
+        .with_label(
+            Label::new_primary(Span::new(match_start, match_end))
+                .with_message("expected `String`, found `Nat`"),
+        )
+        .with_help("all `match` arms must produce values of the same type");
+
+    let diagnostics = [error, warning, match_error];
+
+    let config = Config {
+        display_style: if opts.short {
+            DisplayStyle::Short
+        } else {
+            DisplayStyle::Rich
+        },
+        context_lines: opts.context_lines,
+        glyphs: if opts.unicode {
+            Glyphs::unicode()
+        } else {
+            Glyphs::ascii()
+        },
+        styles: opts.styles,
+        max_width: opts.max_width,
+        registry: Some(registry),
+        ..Config::default()
+    };
+    if opts.html {
+        println!("<!DOCTYPE html>\n<style>\n{}</style>", HTML_STYLESHEET);
+        for diagnostic in &diagnostics {
+            emit_html(io::stdout(), &config, &code_map, diagnostic).unwrap();
+        }
+        return;
+    }
 
     let writer = StandardStream::stderr(opts.color.into());
     for diagnostic in &diagnostics {
-        emit(&mut writer.lock(), &code_map, &diagnostic).unwrap();
+        emit(&mut writer.lock(), &config, &code_map, diagnostic).unwrap();
         println!();
     }
 }
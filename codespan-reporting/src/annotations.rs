@@ -0,0 +1,352 @@
+//! Checking the diagnostics reported for a test fixture against annotations
+//! in its comments, in the style of the UI tests of `rustc`
+
+use codespan::{ByteOffset, ByteSpan, FileMap, LineIndex, Span};
+use std::fmt;
+
+use {Diagnostic, Label, LabelStyle, Severity};
+
+/// A diagnostic that a fixture expects to be reported
+#[derive(Clone, Debug, PartialEq)]
+pub struct Annotation {
+    /// The line that the diagnostic should point to
+    pub line: LineIndex,
+    pub severity: Severity,
+    /// Text that the message of the diagnostic should contain. Any message
+    /// is accepted if this is empty.
+    pub message: String,
+    /// The span of the annotation in the fixture
+    pub span: ByteSpan,
+}
+
+/// Checks the diagnostics reported for a fixture against the annotations in
+/// its comments, which look like this in languages with C-style comments:
+///
+/// ```text
+/// let x: Int = "";  //~ ERROR mismatched types
+/// let y = x + z;
+/// //~^ ERROR unknown variable `z`
+/// //~| WARN unused variable
+/// ```
+///
+/// An annotation starts with the marker, followed by a `^` for each line
+/// above the annotation that the diagnostic points to, or by a `|` to point
+/// to the same line as the previous annotation. Then comes the severity,
+/// which is one of `ERROR`, `WARN`, `NOTE`, `HELP` or `BUG`, followed by some
+/// text that the message of the diagnostic must contain.
+///
+/// Diagnostics and their children point to the line of their primary
+/// label, or of their first label if none of them are primary. Errors,
+/// warnings and bugs must always be annotated, but notes and help messages
+/// only need to be annotated if the fixture has annotations with the same
+/// severity.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnnotationChecker {
+    /// The text that starts an annotation, which should begin with the line
+    /// comment syntax of the language, as in `//~` or `;~`
+    pub marker: String,
+}
+
+impl Default for AnnotationChecker {
+    fn default() -> AnnotationChecker {
+        AnnotationChecker::new("//~")
+    }
+}
+
+impl AnnotationChecker {
+    pub fn new<S: Into<String>>(marker: S) -> AnnotationChecker {
+        AnnotationChecker {
+            marker: marker.into(),
+        }
+    }
+
+    /// Read the annotations in a fixture, returning diagnostics describing
+    /// the annotations that could not be read, if there are any
+    pub fn parse(&self, file: &FileMap) -> Result<Vec<Annotation>, Vec<Diagnostic>> {
+        let mut annotations: Vec<Annotation> = Vec::new();
+        let mut errors = Vec::new();
+        let mut line_start = file.span().start();
+
+        for (i, line) in file.src().split('\n').enumerate() {
+            let line_span = Span::from_offset(line_start, ByteOffset(line.len() as i64));
+            line_start = line_span.end() + ByteOffset(1);
+
+            let start = match line.find(&self.marker) {
+                Some(start) => start,
+                None => continue,
+            };
+            let offset = |offset: usize| line_span.start() + ByteOffset(offset as i64);
+            let span = Span::new(offset(start), offset(line.trim_end().len()));
+            let invalid = |message: String| {
+                Diagnostic::new_error(message)
+                    .with_label(Label::new_primary(span).with_message("in this annotation"))
+            };
+
+            let rest = &line[start + self.marker.len()..];
+            let (line, rest) = match rest.strip_prefix('|') {
+                Some(rest) => match annotations.last() {
+                    Some(previous) => (previous.line, rest),
+                    None => {
+                        let message = "`|` refers to the previous annotation, but there is none";
+                        errors.push(invalid(message.to_owned()));
+                        continue;
+                    },
+                },
+                None => {
+                    let carets = rest.len() - rest.trim_start_matches('^').len();
+                    match i.checked_sub(carets) {
+                        Some(line) => (LineIndex(line as u32), &rest[carets..]),
+                        None => {
+                            let message = "the annotation points above the start of the file";
+                            errors.push(invalid(message.to_owned()));
+                            continue;
+                        },
+                    }
+                },
+            };
+
+            let rest = rest.trim();
+            let (kind, message) = match rest.find(char::is_whitespace) {
+                Some(end) => (&rest[..end], rest[end..].trim_start()),
+                None => (rest, ""),
+            };
+            let severity = match kind {
+                "ERROR" => Severity::Error,
+                "WARN" | "WARNING" => Severity::Warning,
+                "NOTE" => Severity::Note,
+                "HELP" => Severity::Help,
+                "BUG" => Severity::Bug,
+                _ => {
+                    errors.push(
+                        invalid(format!("unknown severity `{}`", kind))
+                            .with_help("expected one of `ERROR`, `WARN`, `NOTE`, `HELP` or `BUG`"),
+                    );
+                    continue;
+                },
+            };
+
+            annotations.push(Annotation {
+                line,
+                severity,
+                message: message.to_owned(),
+                span,
+            });
+        }
+
+        match errors.len() {
+            0 => Ok(annotations),
+            _ => Err(errors),
+        }
+    }
+
+    /// Check the diagnostics reported for a fixture against its annotations,
+    /// returning diagnostics that describe each unexpected diagnostic and
+    /// each annotation without a matching diagnostic. These can be rendered
+    /// with `emit`, using the codemap containing the fixture.
+    pub fn check(&self, file: &FileMap, diagnostics: &[Diagnostic]) -> Vec<Diagnostic> {
+        let annotations = match self.parse(file) {
+            Ok(annotations) => annotations,
+            Err(errors) => return errors,
+        };
+        let mut matched = vec![false; annotations.len()];
+        let mut failures = Vec::new();
+
+        let mut reported = Vec::new();
+        flatten(diagnostics, &mut reported);
+        for diagnostic in reported {
+            let span = location(file, diagnostic);
+            let line = span.and_then(|span| file.find_line(span.start()).ok());
+
+            let annotation = annotations
+                .iter()
+                .zip(&matched)
+                .position(|(annotation, &matched)| {
+                    !matched
+                        && Some(annotation.line) == line
+                        && annotation.severity == diagnostic.severity
+                        && diagnostic.message.contains(&annotation.message)
+                });
+            let is_required = match diagnostic.severity {
+                Severity::Bug | Severity::Error | Severity::Warning => true,
+                Severity::Note | Severity::Help => annotations
+                    .iter()
+                    .any(|annotation| annotation.severity == diagnostic.severity),
+            };
+
+            match annotation {
+                Some(i) => matched[i] = true,
+                None if is_required => {
+                    let failure = Diagnostic::new_error(format!(
+                        "unexpected {}: {}",
+                        Kind(diagnostic.severity),
+                        diagnostic.message,
+                    ));
+                    failures.push(match span {
+                        Some(span) => failure
+                            .with_label(Label::new_primary(span).with_message("reported here")),
+                        None => failure.with_note("the diagnostic does not point to the fixture"),
+                    });
+                },
+                None => {},
+            }
+        }
+
+        for (annotation, _) in annotations
+            .iter()
+            .zip(matched)
+            .filter(|&(_, matched)| !matched)
+        {
+            let message = match annotation.message.as_str() {
+                "" => format!("expected {} was not reported", Kind(annotation.severity)),
+                message => format!(
+                    "expected {} was not reported: {}",
+                    Kind(annotation.severity),
+                    message,
+                ),
+            };
+            failures.push(
+                Diagnostic::new_error(message).with_label(
+                    Label::new_primary(annotation.span)
+                        .with_message(format!("expected on line {}", annotation.line.number())),
+                ),
+            );
+        }
+
+        failures
+    }
+}
+
+/// The name of a severity in the messages of failures
+struct Kind(Severity);
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Severity::Bug => "bug".fmt(f),
+            severity => severity.fmt(f),
+        }
+    }
+}
+
+/// Collect diagnostics and their children, in the order they are rendered
+fn flatten<'a>(diagnostics: &'a [Diagnostic], flattened: &mut Vec<&'a Diagnostic>) {
+    for diagnostic in diagnostics {
+        flattened.push(diagnostic);
+        flatten(&diagnostic.children, flattened);
+    }
+}
+
+/// The span of the label that a diagnostic points to in a file
+fn location(file: &FileMap, diagnostic: &Diagnostic) -> Option<ByteSpan> {
+    let labels = diagnostic
+        .labels
+        .iter()
+        .filter(|label| file.span().contains(label.span));
+    let mut first = None;
+    for label in labels {
+        if label.style == LabelStyle::Primary {
+            return Some(label.span);
+        }
+        first = first.or(Some(label.span));
+    }
+    first
+}
+
+#[cfg(test)]
+mod tests {
+    use codespan::CodeMap;
+    use termcolor::NoColor;
+
+    use super::*;
+    use emitter::{emit, Config};
+
+    const SOURCE: &str = "\
+(define x \"\") ;~ ERROR mismatched
+(+ x y)
+;~^ ERROR unknown variable `y`
+;~| WARN
+(print z) ;~ HELP
+";
+
+    fn span(file: &FileMap, line: u32, column: u32, len: i64) -> ByteSpan {
+        let start = file.byte_index(line.into(), column.into()).unwrap();
+        Span::from_offset(start, ByteOffset(len))
+    }
+
+    #[test]
+    fn parse() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), SOURCE.to_owned());
+        let checker = AnnotationChecker::new(";~");
+        let annotations = checker.parse(&file).unwrap();
+
+        let expected = vec![
+            (0, Severity::Error, "mismatched"),
+            (1, Severity::Error, "unknown variable `y`"),
+            (1, Severity::Warning, ""),
+            (4, Severity::Help, ""),
+        ];
+        let annotations = annotations
+            .iter()
+            .map(|annotation| {
+                let line = annotation.line.0;
+                (line, annotation.severity, annotation.message.as_str())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(annotations, expected);
+
+        let file = codemap.add_filemap("test".into(), "a\n //~^^ ERROR\n//~ OOPS".to_owned());
+        let errors = AnnotationChecker::default().parse(&file).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "the annotation points above the start of the file",
+        );
+        assert_eq!(errors[0].labels[0].span, span(&file, 1, 1, 11));
+        assert_eq!(errors[1].message, "unknown severity `OOPS`");
+    }
+
+    #[test]
+    fn check() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), SOURCE.to_owned());
+        let diagnostics = vec![
+            Diagnostic::new_error("mismatched types")
+                .with_label(Label::new_secondary(span(&file, 0, 0, 13)))
+                .with_child(
+                    Diagnostic::new_note("defined here")
+                        .with_label(Label::new_primary(span(&file, 0, 10, 2))),
+                ),
+            Diagnostic::new_warning("unused variable `x`")
+                .with_label(Label::new_primary(span(&file, 1, 3, 1))),
+            Diagnostic::new_help("did you mean `x`?")
+                .with_label(Label::new_primary(span(&file, 1, 5, 1))),
+            Diagnostic::new_error("aborting"),
+        ];
+
+        let failures = AnnotationChecker::new(";~").check(&file, &diagnostics);
+        let mut writer = NoColor::new(Vec::new());
+        for failure in &failures {
+            emit(&mut writer, &Config::default(), &codemap, failure).unwrap();
+        }
+
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "\
+error: unexpected help: did you mean `x`?
+- <test>:2:6
+2 | (+ x y)
+  |      ^ reported here
+error: unexpected error: aborting
+ = note: the diagnostic does not point to the fixture
+error: expected error was not reported: unknown variable `y`
+- <test>:3:1
+3 | ;~^ ERROR unknown variable `y`
+  | ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^ expected on line 2
+error: expected help was not reported
+- <test>:5:11
+5 | (print z) ;~ HELP
+  |           ^^^^^^^ expected on line 5
+",
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! Renders diagnostics that have been serialized as JSON lines, so that tools
+//! written in other languages can use the output of `codespan-reporting`, and
+//! so that the diagnostics of a previous build can be shown again.
+//!
+//! Each line of input is a `Diagnostic`, as serialized with the
+//! `serialization` feature. The spans of the diagnostics are byte indices into
+//! a `CodeMap` containing the source files given on the command line, in the
+//! order that they are given. The first file starts at index 1, and each
+//! following file starts one byte after the end of the previous file.
+
+extern crate codespan;
+extern crate codespan_reporting;
+extern crate serde_json;
+extern crate structopt;
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+use codespan::CodeMap;
+use codespan_reporting::termcolor::StandardStream;
+use codespan_reporting::{ColorArg, Config, Diagnostic, DisplayStyle, Emitter, Glyphs,
+                         HtmlEmitter, JsonEmitter, SarifEmitter, SarifTool, TermEmitter,
+                         HTML_STYLESHEET};
+
+/// The formats that diagnostics can be rendered in
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    /// Annotated snippets of source code, as written by `emit`
+    Rich,
+    /// One line per label
+    Short,
+    /// One JSON object per diagnostic, as written by `emit_json`
+    Json,
+    /// An HTML document, as written by `emit_html`
+    Html,
+    /// A SARIF log
+    Sarif,
+}
+
+impl Format {
+    /// The names that a format can be parsed from
+    pub const VARIANTS: &'static [&'static str] = &["rich", "short", "json", "html", "sarif"];
+}
+
+impl FromStr for Format {
+    type Err = &'static str;
+
+    fn from_str(src: &str) -> Result<Format, &'static str> {
+        match src {
+            _ if src.eq_ignore_ascii_case("rich") => Ok(Format::Rich),
+            _ if src.eq_ignore_ascii_case("short") => Ok(Format::Short),
+            _ if src.eq_ignore_ascii_case("json") => Ok(Format::Json),
+            _ if src.eq_ignore_ascii_case("html") => Ok(Format::Html),
+            _ if src.eq_ignore_ascii_case("sarif") => Ok(Format::Sarif),
+            _ => Err("valid values: rich, short, json, html, sarif"),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "codespan-render")]
+pub struct Opts {
+    /// Configure coloring of output
+    #[structopt(
+        long = "color",
+        parse(try_from_str),
+        default_value = "auto",
+        raw(possible_values = "ColorArg::VARIANTS", case_insensitive = "true")
+    )]
+    pub color: ColorArg,
+    /// The format to render the diagnostics in
+    #[structopt(
+        long = "format",
+        parse(try_from_str),
+        default_value = "rich",
+        raw(possible_values = "Format::VARIANTS", case_insensitive = "true")
+    )]
+    pub format: Format,
+    /// The number of lines of context to show around each label
+    #[structopt(long = "context", default_value = "0")]
+    pub context_lines: usize,
+    /// Draw snippets with Unicode box-drawing characters
+    #[structopt(long = "unicode")]
+    pub unicode: bool,
+    /// Trim source lines to fit within this many columns, defaulting to the
+    /// width of the terminal when writing to one
+    #[structopt(long = "max-width")]
+    pub max_width: Option<usize>,
+    /// The name of the tool that reported the diagnostics, as recorded in
+    /// SARIF logs
+    #[structopt(long = "tool", default_value = "codespan-render")]
+    pub tool: String,
+    /// Read the diagnostics from this file, rather than from standard input
+    #[structopt(long = "diagnostics", parse(from_os_str))]
+    pub diagnostics: Option<PathBuf>,
+    /// The source files that the diagnostics point into, in the order that
+    /// they were added to the codemap
+    #[structopt(parse(from_os_str))]
+    pub files: Vec<PathBuf>,
+}
+
+fn main() {
+    let opts = Opts::from_args();
+
+    if let Err(error) = render(&opts) {
+        eprintln!("codespan-render: {}", error);
+        process::exit(1);
+    }
+}
+
+fn render(opts: &Opts) -> Result<(), String> {
+    let mut codemap = CodeMap::new();
+    for path in &opts.files {
+        codemap
+            .add_filemap_from_disk(path.clone())
+            .map_err(|error| format!("could not read `{}`: {}", path.display(), error))?;
+    }
+
+    let input: Box<dyn BufRead> = match opts.diagnostics {
+        Some(ref path) => match File::open(path) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(error) => return Err(format!("could not read `{}`: {}", path.display(), error)),
+        },
+        None => Box::new(BufReader::new(io::stdin())),
+    };
+
+    let config = Config {
+        display_style: match opts.format {
+            Format::Short => DisplayStyle::Short,
+            _ => DisplayStyle::Rich,
+        },
+        context_lines: opts.context_lines,
+        glyphs: if opts.unicode {
+            Glyphs::unicode()
+        } else {
+            Glyphs::ascii()
+        },
+        max_width: opts.max_width,
+        ..Config::default()
+    };
+    let mut emitter: Box<dyn Emitter> = match opts.format {
+        Format::Rich | Format::Short => {
+            // The emitter falls back to the width of the terminal that the
+            // output is written to
+            let writer = StandardStream::stdout(opts.color.into());
+            Box::new(TermEmitter::new(writer, config))
+        },
+        Format::Json => Box::new(JsonEmitter::new(io::stdout(), config)),
+        Format::Html => {
+            println!("<!DOCTYPE html>\n<style>\n{}</style>", HTML_STYLESHEET);
+            Box::new(HtmlEmitter::new(io::stdout(), config))
+        },
+        Format::Sarif => {
+            let tool = SarifTool::new(opts.tool.clone());
+            Box::new(SarifEmitter::new(io::stdout(), tool, config))
+        },
+    };
+
+    let write_error = |error: io::Error| format!("could not write the diagnostics: {}", error);
+    for (i, line) in input.lines().enumerate() {
+        let line = line.map_err(|error| format!("could not read the diagnostics: {}", error))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let diagnostic = serde_json::from_str::<Diagnostic>(&line)
+            .map_err(|error| format!("invalid diagnostic on line {}: {}", i + 1, error))?;
+        emitter.emit(&codemap, &diagnostic).map_err(write_error)?;
+    }
+
+    emitter.finish().map_err(write_error)?;
+    io::stdout().flush().map_err(write_error)
+}
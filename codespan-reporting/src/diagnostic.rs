@@ -1,11 +1,13 @@
 //! Diagnostic reporting support for the codespan crate
 
 use codespan::ByteSpan;
+use std::fmt;
 
 use Severity;
 
 /// A style for the label
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serialization", derive(Deserialize, Serialize))]
 pub enum LabelStyle {
     /// The main focus of the diagnostic
     Primary,
@@ -14,12 +16,17 @@ pub enum LabelStyle {
 }
 
 /// A label describing an underlined region of code associated with a diagnostic
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization", derive(Deserialize, Serialize))]
 pub struct Label {
     /// The span we are going to include in the final snippet.
     pub span: ByteSpan,
     /// A message to provide some additional information for the underlined code.
     pub message: Option<String>,
+    /// The id of a message in a `Catalog` that `message` is replaced with
+    /// when the diagnostic is emitted, if the catalog has a translation
+    #[cfg_attr(feature = "serialization", serde(default))]
+    pub message_id: Option<String>,
     /// The style to use for the label.
     pub style: LabelStyle,
 }
@@ -29,6 +36,7 @@ impl Label {
         Label {
             span,
             message: None,
+            message_id: None,
             style,
         }
     }
@@ -45,10 +53,116 @@ impl Label {
         self.message = Some(message.into());
         self
     }
+
+    pub fn with_message_id<S: Into<String>>(mut self, message_id: S) -> Label {
+        self.message_id = Some(message_id.into());
+        self
+    }
+}
+
+/// A note or help message that is displayed after the labelled snippets of a
+/// diagnostic
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization", derive(Deserialize, Serialize))]
+pub struct Note {
+    /// The severity of the note, usually either `Severity::Note` or `Severity::Help`
+    pub severity: Severity,
+    /// The message of the note
+    pub message: String,
+    /// The id of a message in a `Catalog` that `message` is replaced with
+    /// when the diagnostic is emitted, if the catalog has a translation
+    #[cfg_attr(feature = "serialization", serde(default))]
+    pub message_id: Option<String>,
+}
+
+impl Note {
+    pub fn new<S: Into<String>>(severity: Severity, message: S) -> Note {
+        Note {
+            severity,
+            message: message.into(),
+            message_id: None,
+        }
+    }
+
+    pub fn new_note<S: Into<String>>(message: S) -> Note {
+        Note::new(Severity::Note, message)
+    }
+
+    pub fn new_help<S: Into<String>>(message: S) -> Note {
+        Note::new(Severity::Help, message)
+    }
+
+    pub fn with_message_id<S: Into<String>>(mut self, message_id: S) -> Note {
+        self.message_id = Some(message_id.into());
+        self
+    }
+}
+
+/// How confident we are that a suggestion is what the user intended
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serialization", derive(Deserialize, Serialize))]
+pub enum Applicability {
+    /// The suggestion is definitely what the user intended, and can be applied
+    /// automatically by tools
+    MachineApplicable,
+    /// The suggestion may be what the user intended, but it is uncertain. It
+    /// should result in valid code if applied.
+    MaybeIncorrect,
+    /// The suggestion contains placeholders like `(...)` or `{ /* fields */ }`
+    /// that need to be filled in by the user before it can be applied
+    HasPlaceholders,
+}
+
+/// A suggested change to the source code, replacing the code in a span with
+/// some new text
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization", derive(Deserialize, Serialize))]
+pub struct Suggestion {
+    /// The span of code that should be replaced. This can be empty if the
+    /// suggestion is an insertion.
+    pub span: ByteSpan,
+    /// The text to replace the span with. This can be empty if the suggestion
+    /// is a deletion.
+    pub replacement: String,
+    /// A message describing the suggestion, for example "try adding a semicolon"
+    pub message: String,
+    /// The id of a message in a `Catalog` that `message` is replaced with
+    /// when the diagnostic is emitted, if the catalog has a translation
+    #[cfg_attr(feature = "serialization", serde(default))]
+    pub message_id: Option<String>,
+    /// How confident we are that the suggestion is correct
+    pub applicability: Applicability,
+}
+
+impl Suggestion {
+    pub fn new<S, R>(
+        span: ByteSpan,
+        message: S,
+        replacement: R,
+        applicability: Applicability,
+    ) -> Suggestion
+    where
+        S: Into<String>,
+        R: Into<String>,
+    {
+        Suggestion {
+            span,
+            replacement: replacement.into(),
+            message: message.into(),
+            message_id: None,
+            applicability,
+        }
+    }
+
+    pub fn with_message_id<S: Into<String>>(mut self, message_id: S) -> Suggestion {
+        self.message_id = Some(message_id.into());
+        self
+    }
 }
 
 /// Represents a diagnostic message and associated child messages.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialization", derive(Deserialize, Serialize))]
 pub struct Diagnostic {
     /// The overall severity of the diagnostic
     pub severity: Severity,
@@ -56,9 +170,29 @@ pub struct Diagnostic {
     pub code: Option<String>,
     /// The main message associated with this diagnostic
     pub message: String,
+    /// The id of a message in a `Catalog` that `message` is replaced with
+    /// when the diagnostic is emitted, if the catalog has a translation
+    #[cfg_attr(feature = "serialization", serde(default))]
+    pub message_id: Option<String>,
     /// The labelled spans marking the regions of code that cause this
     /// diagnostic to be raised
+    #[cfg_attr(feature = "serialization", serde(default))]
     pub labels: Vec<Label>,
+    /// Notes and help messages that are displayed at the end of the diagnostic
+    #[cfg_attr(feature = "serialization", serde(default))]
+    pub notes: Vec<Note>,
+    /// Sub-diagnostics that point to other related locations, for example
+    /// "note: previous definition here"
+    #[cfg_attr(feature = "serialization", serde(default))]
+    pub children: Vec<Diagnostic>,
+    /// Suggested changes to the source code that could resolve the diagnostic
+    #[cfg_attr(feature = "serialization", serde(default))]
+    pub suggestions: Vec<Suggestion>,
+    /// Named values used to fill in the messages of the diagnostic when they
+    /// are translated with a `Catalog`, which are also available to the
+    /// messages of its children
+    #[cfg_attr(feature = "serialization", serde(default))]
+    pub args: Vec<(String, String)>,
 }
 
 impl Diagnostic {
@@ -67,7 +201,12 @@ impl Diagnostic {
             severity,
             code: None,
             message: message.into(),
+            message_id: None,
             labels: Vec::new(),
+            notes: Vec::new(),
+            children: Vec::new(),
+            suggestions: Vec::new(),
+            args: Vec::new(),
         }
     }
 
@@ -96,6 +235,11 @@ impl Diagnostic {
         self
     }
 
+    pub fn with_message_id<S: Into<String>>(mut self, message_id: S) -> Diagnostic {
+        self.message_id = Some(message_id.into());
+        self
+    }
+
     pub fn with_label(mut self, label: Label) -> Diagnostic {
         self.labels.push(label);
         self
@@ -105,4 +249,119 @@ impl Diagnostic {
         self.labels.extend(labels);
         self
     }
+
+    pub fn with_note<S: Into<String>>(mut self, message: S) -> Diagnostic {
+        self.notes.push(Note::new_note(message));
+        self
+    }
+
+    pub fn with_help<S: Into<String>>(mut self, message: S) -> Diagnostic {
+        self.notes.push(Note::new_help(message));
+        self
+    }
+
+    pub fn with_notes<Notes: IntoIterator<Item = Note>>(mut self, notes: Notes) -> Diagnostic {
+        self.notes.extend(notes);
+        self
+    }
+
+    pub fn with_child(mut self, child: Diagnostic) -> Diagnostic {
+        self.children.push(child);
+        self
+    }
+
+    pub fn with_children<Children: IntoIterator<Item = Diagnostic>>(
+        mut self,
+        children: Children,
+    ) -> Diagnostic {
+        self.children.extend(children);
+        self
+    }
+
+    pub fn with_suggestion(mut self, suggestion: Suggestion) -> Diagnostic {
+        self.suggestions.push(suggestion);
+        self
+    }
+
+    pub fn with_suggestions<Suggestions: IntoIterator<Item = Suggestion>>(
+        mut self,
+        suggestions: Suggestions,
+    ) -> Diagnostic {
+        self.suggestions.extend(suggestions);
+        self
+    }
+
+    /// Add an argument for the translated messages of the diagnostic, which
+    /// replaces `{ $name }` in the messages of the catalog
+    pub fn with_arg<S: Into<String>, T: fmt::Display>(mut self, name: S, value: T) -> Diagnostic {
+        self.args.push((name.into(), value.to_string()));
+        self
+    }
+}
+
+/// Types that can be converted into a diagnostic
+///
+/// This is usually implemented with `#[derive(IntoDiagnostic)]` from the
+/// `codespan-reporting-derive` crate, which also implements
+/// `From<T> for Diagnostic`.
+pub trait IntoDiagnostic {
+    fn into_diagnostic(self) -> Diagnostic;
+}
+
+impl IntoDiagnostic for Diagnostic {
+    fn into_diagnostic(self) -> Diagnostic {
+        self
+    }
+}
+
+#[cfg(all(test, feature = "serialization"))]
+mod tests {
+    use codespan::{ByteIndex, Span};
+    use serde_json;
+
+    use super::*;
+
+    #[test]
+    fn serialization_round_trip() {
+        let span = Span::new(ByteIndex(1), ByteIndex(4));
+        let diagnostic = Diagnostic::new_error("mismatched types")
+            .with_code("E0308")
+            .with_arg("expected", "Int")
+            .with_label(Label::new_primary(span).with_message("expected `Int`"))
+            .with_label(Label::new_secondary(span))
+            .with_help("try converting it")
+            .with_suggestion(Suggestion::new(
+                span,
+                "use a number",
+                "1",
+                Applicability::MaybeIncorrect,
+            ))
+            .with_child(Diagnostic::new_bug("while checking this"));
+
+        let json = serde_json::to_string(&diagnostic).unwrap();
+        let round_tripped = serde_json::from_str::<Diagnostic>(&json).unwrap();
+        assert_eq!(round_tripped, diagnostic);
+    }
+
+    #[test]
+    fn deserialize_with_defaults() {
+        let json = r#"{
+            "severity": "Warning",
+            "code": null,
+            "message": "unused variable",
+            "labels": [
+                {
+                    "span": { "start": 5, "end": 6 },
+                    "message": null,
+                    "style": "Primary"
+                }
+            ]
+        }"#;
+
+        let span = Span::new(ByteIndex(5), ByteIndex(6));
+        assert_eq!(
+            serde_json::from_str::<Diagnostic>(json).unwrap(),
+            Diagnostic::new_warning("unused variable").with_label(Label::new_primary(span)),
+        );
+    }
 }
@@ -1,8 +1,14 @@
-use codespan::CodeMap;
+use codespan::{ByteIndexError, ByteOffset, ByteSpan, CodeMap, ColumnIndex, FileMap, LineIndex};
+use std::sync::Arc;
 use std::{fmt, io};
-use termcolor::{Color, ColorSpec, WriteColor};
+use termcolor::{ColorSpec, WriteColor};
+use unicode_width::UnicodeWidthStr;
 
-use {Diagnostic, LabelStyle};
+use explain::Registry;
+use locale::Catalog;
+use snippet::{Row, Snippet};
+use styles::Styles;
+use {Diagnostic, Label, LabelStyle, Severity, Suggestion};
 
 struct Pad<T>(T, usize);
 
@@ -15,114 +21,1668 @@ impl<T: fmt::Display> fmt::Display for Pad<T> {
     }
 }
 
-pub fn emit<W>(mut writer: W, codemap: &CodeMap, diagnostic: &Diagnostic) -> io::Result<()>
+/// The overall format used to render diagnostics
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DisplayStyle {
+    /// Show the header of each diagnostic, followed by annotated snippets of
+    /// the source code, notes, suggestions, and children
+    #[default]
+    Rich,
+    /// Show a single line per label, in the format commonly understood by
+    /// editors and log parsers:
+    ///
+    /// ```text
+    /// test:3:9: error[E0001]: Unexpected type in `+` application: Expected integer but got string
+    /// ```
+    ///
+    /// Notes and suggestions are omitted.
+    Short,
+}
+
+/// The characters used to draw snippets
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Glyphs {
+    /// Underlines the source code of primary labels
+    pub primary_underline: char,
+    /// Underlines the source code of secondary labels
+    pub secondary_underline: char,
+    /// Connects label messages to their underlines, and is drawn beside the
+    /// lines covered by a multi-line label
+    pub vertical: char,
+    /// Connects the start and end of a multi-line label to the vertical
+    pub horizontal: char,
+    /// Drawn in place of the vertical on the first line of a multi-line label
+    /// that starts at the beginning of a line
+    pub multiline_start: char,
+    /// The corner drawn where a multi-line label starts partway through a line
+    pub multiline_top_left: char,
+    /// The corner drawn where a multi-line label ends
+    pub multiline_bottom_left: char,
+    /// Separates line numbers from source code
+    pub gutter: char,
+    /// Precedes notes that are shown beneath the snippets
+    pub note: char,
+}
+
+impl Glyphs {
+    /// Glyphs that only use ASCII characters:
+    ///
+    /// ```text
+    /// 1 |     (define (foo x)
+    ///   |  ___________^
+    /// 2 | |     (bar x))
+    ///   | |____________^ here
+    /// ```
+    pub fn ascii() -> Glyphs {
+        Glyphs {
+            primary_underline: '^',
+            secondary_underline: '-',
+            vertical: '|',
+            horizontal: '_',
+            multiline_start: '/',
+            multiline_top_left: ' ',
+            multiline_bottom_left: '|',
+            gutter: '|',
+            note: '=',
+        }
+    }
+
+    /// Glyphs that use Unicode box-drawing characters:
+    ///
+    /// ```text
+    /// 1 │     (define (foo x)
+    ///   │ ╭───────────━
+    /// 2 │ │     (bar x))
+    ///   │ ╰────────────━ here
+    /// ```
+    pub fn unicode() -> Glyphs {
+        Glyphs {
+            primary_underline: '━',
+            secondary_underline: '─',
+            vertical: '│',
+            horizontal: '─',
+            multiline_start: '╭',
+            multiline_top_left: '╭',
+            multiline_bottom_left: '╰',
+            gutter: '│',
+            note: '=',
+        }
+    }
+}
+
+impl Default for Glyphs {
+    fn default() -> Glyphs {
+        Glyphs::ascii()
+    }
+}
+
+/// Configures how diagnostics are rendered by `emit`
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The format used to render diagnostics
+    pub display_style: DisplayStyle,
+    /// The number of lines of source to show before and after each label.
+    /// Gaps between the lines shown for labels in the same file are marked
+    /// with `...`.
+    pub context_lines: usize,
+    /// Whether the short display style includes the position at the end of
+    /// each label, as in `test:3:9-3:11`
+    pub end_positions: bool,
+    /// The number of columns between tab stops, used when expanding tabs in
+    /// source code. A width of 0 is treated as 1, so that tabs are never
+    /// hidden.
+    pub tab_width: usize,
+    /// The characters used to draw snippets
+    pub glyphs: Glyphs,
+    /// Whether line numbers are shown beside source code
+    pub show_line_numbers: bool,
+    /// The colors used when the output supports them
+    pub styles: Styles,
+    /// The maximum width of the rows of a snippet, including line numbers.
+    /// Longer lines of source are trimmed to the columns around their
+    /// labels, with `...` marking the hidden source.
+    ///
+    /// When this is `None`, diagnostics written to a terminal are trimmed to
+    /// its width, as given by `terminal_width`, and diagnostics written
+    /// anywhere else are not trimmed.
+    pub max_width: Option<usize>,
+    /// The messages used to translate diagnostics, and the severity names
+    /// and other text written around them
+    pub catalog: Catalog,
+    /// The explanations of diagnostic codes. Diagnostics with a code that
+    /// has an explanation end with a hint pointing to it, and the
+    /// explanation is included in the output of `emit_json`.
+    pub registry: Option<Registry>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            display_style: DisplayStyle::default(),
+            context_lines: 0,
+            end_positions: false,
+            tab_width: 4,
+            glyphs: Glyphs::default(),
+            show_line_numbers: true,
+            styles: Styles::default(),
+            max_width: None,
+            catalog: Catalog::default(),
+            registry: None,
+        }
+    }
+}
+
+/// The width of the terminal that standard error is connected to, or failing
+/// that, standard output. This is `None` if neither is connected to a
+/// terminal.
+pub fn terminal_width() -> Option<usize> {
+    #[cfg(unix)]
+    let size = terminal_size::terminal_size_using_fd(2)
+        .or_else(|| terminal_size::terminal_size_using_fd(1));
+    #[cfg(windows)]
+    let size = {
+        use std::os::windows::io::AsRawHandle;
+        terminal_size::terminal_size_using_handle(io::stderr().as_raw_handle())
+            .or_else(|| terminal_size::terminal_size_using_handle(io::stdout().as_raw_handle()))
+    };
+    #[cfg(not(any(unix, windows)))]
+    let size = None;
+
+    size.map(|(terminal_size::Width(width), _)| usize::from(width))
+}
+
+/// The width to trim the output to, which is the width of the terminal if
+/// none is configured and `writer` supports color, and so is a terminal
+fn detect_max_width<W: WriteColor>(writer: &W, config: &Config) -> Option<usize> {
+    match config.max_width {
+        None if writer.supports_color() => terminal_width(),
+        max_width => max_width,
+    }
+}
+
+pub fn emit<W>(
+    mut writer: W,
+    config: &Config,
+    codemap: &CodeMap,
+    diagnostic: &Diagnostic,
+) -> io::Result<()>
 where
     W: WriteColor,
 {
-    let supports_color = writer.supports_color();
-    let line_location_color = ColorSpec::new()
-        // Blue is really difficult to see on the standard windows command line
-        .set_fg(Some(if cfg!(windows) { Color::Cyan } else { Color::Blue }))
-        .clone();
-    let diagnostic_color = ColorSpec::new()
-        .set_fg(Some(diagnostic.severity.color()))
-        .clone();
+    let detected;
+    let config = match detect_max_width(&writer, config) {
+        max_width if max_width == config.max_width => config,
+        max_width => {
+            detected = Config {
+                max_width,
+                ..config.clone()
+            };
+            &detected
+        },
+    };
+
+    let diagnostic = &config.catalog.localize(diagnostic);
+    let mut writer = ColorWriter::new(&mut writer, &config.styles);
+    if config.display_style == DisplayStyle::Short {
+        return emit_short(&mut writer, config, codemap, diagnostic);
+    }
+
+    let layout = Layout::new(config, codemap, diagnostic);
+    // Line numbers are aligned across the diagnostic and all of its children
+    let gutter_width = layout.gutter_width(config);
+
+    render_layout(&mut writer, config, &layout, gutter_width)
+}
+
+/// A backend that diagnostics can be emitted to
+///
+/// Diagnostics are emitted one at a time, and the batch is completed by a
+/// call to `finish`. Formats that wrap all of the diagnostics in a single
+/// document, like SARIF, may buffer the diagnostics until then.
+pub trait Emitter {
+    /// Emit a single diagnostic
+    fn emit(&mut self, codemap: &CodeMap, diagnostic: &Diagnostic) -> io::Result<()>;
+
+    /// Complete the batch of diagnostics emitted so far, writing anything
+    /// that was buffered and flushing the output. Subsequent diagnostics start
+    /// a new batch.
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+impl<E: Emitter + ?Sized> Emitter for &mut E {
+    fn emit(&mut self, codemap: &CodeMap, diagnostic: &Diagnostic) -> io::Result<()> {
+        (**self).emit(codemap, diagnostic)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        (**self).finish()
+    }
+}
+
+impl<E: Emitter + ?Sized> Emitter for Box<E> {
+    fn emit(&mut self, codemap: &CodeMap, diagnostic: &Diagnostic) -> io::Result<()> {
+        (**self).emit(codemap, diagnostic)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        (**self).finish()
+    }
+}
+
+/// Renders diagnostics to a terminal, using `emit`
+#[derive(Clone, Debug)]
+pub struct TermEmitter<W> {
+    pub writer: W,
+    pub config: Config,
+}
+
+impl<W: WriteColor> TermEmitter<W> {
+    /// Create an emitter, which trims the output to the width of the
+    /// terminal if `config.max_width` is `None` and `writer` is a terminal
+    pub fn new(writer: W, mut config: Config) -> TermEmitter<W> {
+        config.max_width = detect_max_width(&writer, &config);
+        TermEmitter { writer, config }
+    }
+}
+
+impl<W: WriteColor> Emitter for TermEmitter<W> {
+    fn emit(&mut self, codemap: &CodeMap, diagnostic: &Diagnostic) -> io::Result<()> {
+        emit(&mut self.writer, &self.config, codemap, diagnostic)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Emits each diagnostic to several emitters at once
+#[derive(Default)]
+pub struct TeeEmitter<'a> {
+    pub emitters: Vec<Box<dyn Emitter + 'a>>,
+}
+
+impl<'a> TeeEmitter<'a> {
+    pub fn new() -> TeeEmitter<'a> {
+        TeeEmitter::default()
+    }
+
+    pub fn with_emitter<E: Emitter + 'a>(mut self, emitter: E) -> TeeEmitter<'a> {
+        self.emitters.push(Box::new(emitter));
+        self
+    }
+}
+
+impl<'a> Emitter for TeeEmitter<'a> {
+    fn emit(&mut self, codemap: &CodeMap, diagnostic: &Diagnostic) -> io::Result<()> {
+        for emitter in &mut self.emitters {
+            emitter.emit(codemap, diagnostic)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        for emitter in &mut self.emitters {
+            emitter.finish()?;
+        }
+        Ok(())
+    }
+}
+
+/// Find the file that `span` points into.
+///
+/// Spans that are not in the codemap, or that do not lie within a single
+/// file on character boundaries, can't be displayed, and result in an error
+/// describing the problem. These usually come from a bug in the code that
+/// created the diagnostic, so a warning is logged as well.
+pub fn find_file(codemap: &CodeMap, span: ByteSpan) -> Result<&Arc<FileMap>, String> {
+    let reason = match codemap.find_file(span.start()) {
+        None => format!("the span {} is not in the codemap", span),
+        Some(file) => match file
+            .location(span.start())
+            .and_then(|_| file.location(span.end()))
+        {
+            Ok(_) => return Ok(file),
+            Err(ByteIndexError::OutOfBounds { .. }) => {
+                format!("the span {} extends past the end of {}", span, file.name())
+            },
+            Err(ByteIndexError::InvalidCharBoundary { .. }) => {
+                format!("the span {} is not on a character boundary", span)
+            },
+        },
+    };
+
+    warn!("invalid span in diagnostic: {}", reason);
+    Err(reason)
+}
+
+/// The location of a span within the file that it points into
+#[derive(Clone, Debug)]
+pub struct SpanLocation<'a> {
+    pub file: &'a Arc<FileMap>,
+    pub span: ByteSpan,
+    /// The line and column of the start of the span
+    pub start: (LineIndex, ColumnIndex),
+    /// The line and column of the end of the span
+    pub end: (LineIndex, ColumnIndex),
+}
+
+impl<'a> SpanLocation<'a> {
+    /// Find the location of a span, returning an error if it is invalid, as
+    /// with `find_file`
+    pub fn new(codemap: &'a CodeMap, span: ByteSpan) -> Result<SpanLocation<'a>, String> {
+        let file = find_file(codemap, span)?;
+
+        Ok(SpanLocation {
+            file,
+            span,
+            start: file.location(span.start()).expect("start"),
+            end: file.location(span.end()).expect("end"),
+        })
+    }
+
+    /// The offset of the start of the span from the start of its file
+    pub fn byte_offset(&self) -> ByteOffset {
+        self.span.start() - self.file.span().start()
+    }
+}
+
+/// The message of a note shown in place of a label with an invalid span
+pub fn invalid_label_note(catalog: &Catalog, label: &Label, reason: &str) -> String {
+    let mut args = vec![("reason".to_owned(), reason.to_owned())];
+    let id = match label.message {
+        Some(ref message) => {
+            args.push(("label".to_owned(), message.clone()));
+            "invalid-label-with-message"
+        },
+        None => "invalid-label",
+    };
+    catalog.format(id, &args).unwrap_or_else(|| id.to_owned())
+}
+
+/// A diagnostic with its labels laid out into snippets, shared by the
+/// terminal and HTML renderers
+pub struct Layout<'a> {
+    pub diagnostic: &'a Diagnostic,
+    pub parts: Vec<Part<'a>>,
+    /// Each suggestion, with a snippet of the source code once it has been
+    /// applied
+    pub suggestions: Vec<(&'a Suggestion, Option<Snippet<'a>>)>,
+    pub children: Vec<Layout<'a>>,
+}
+
+pub enum Part<'a> {
+    /// A label with a span that is not in the codemap, or that does not lie
+    /// within its file, along with the reason why
+    Invalid(&'a Label, String),
+    /// The labels pointing into a single file
+    Snippet(Snippet<'a>),
+}
+
+impl<'a> Layout<'a> {
+    pub fn new(config: &Config, codemap: &'a CodeMap, diagnostic: &'a Diagnostic) -> Layout<'a> {
+        // Group the labels by the file that they point into, so that each file
+        // is only displayed once
+        let mut groups: Vec<(&Arc<FileMap>, Vec<&Label>)> = Vec::new();
+        let mut parts = Vec::new();
+        for label in &diagnostic.labels {
+            match find_file(codemap, label.span) {
+                Err(reason) => parts.push(Part::Invalid(label, reason)),
+                Ok(file) => match groups
+                    .iter()
+                    .position(|&(group_file, _)| Arc::ptr_eq(group_file, file))
+                {
+                    Some(i) => groups[i].1.push(label),
+                    None => groups.push((file, vec![label])),
+                },
+            }
+        }
+
+        parts.extend(
+            groups
+                .into_iter()
+                .map(|(file, labels)| Part::Snippet(Snippet::new(file, &labels, config))),
+        );
+
+        Layout {
+            diagnostic,
+            parts,
+            suggestions: diagnostic
+                .suggestions
+                .iter()
+                .map(|suggestion| {
+                    let snippet = find_file(codemap, suggestion.span)
+                        .ok()
+                        .map(|file| Snippet::suggestion(file, suggestion, config));
+                    (suggestion, snippet)
+                })
+                .collect(),
+            children: diagnostic
+                .children
+                .iter()
+                .map(|child| Layout::new(config, codemap, child))
+                .collect(),
+        }
+    }
+
+    fn snippets(&self) -> impl Iterator<Item = &Snippet<'a>> {
+        let parts = self.parts.iter().filter_map(|part| match *part {
+            Part::Invalid(..) => None,
+            Part::Snippet(ref snippet) => Some(snippet),
+        });
+        let suggestions = self
+            .suggestions
+            .iter()
+            .filter_map(|(_, snippet)| snippet.as_ref());
+
+        parts.chain(suggestions)
+    }
+
+    /// The width needed to display the largest line number, or zero if line
+    /// numbers are not shown
+    pub fn gutter_width(&self, config: &Config) -> usize {
+        if !config.show_line_numbers {
+            return 0;
+        }
+
+        self.snippets()
+            .flat_map(|snippet| snippet.rows.iter())
+            .filter_map(|row| match *row {
+                Row::Source(line, _) => Some(line.number().to_string().len()),
+                Row::Added(_) | Row::Annotation(_) | Row::Elision => None,
+            })
+            .chain(self.children.iter().map(|child| child.gutter_width(config)))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// The parts of a rendered diagnostic that can be styled
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Style {
+    /// The header of a diagnostic or suggestion
+    Header,
+    /// The severity and code in a header
+    HeaderSeverity(Severity),
+    /// The message in a header, or the location before it in the short
+    /// display style
+    HeaderMessage,
+    /// The file location above a snippet
+    Location,
+    /// Line numbers, and the gutter beside them
+    Gutter,
+    /// A note or help message beneath the snippets
+    Footer,
+    /// The severity of a note or help message
+    FooterSeverity(Severity),
+    /// Underlines and label messages
+    Label(Severity, LabelStyle),
+}
+
+/// A destination for rendered diagnostics, which marks the text that it is
+/// given with styles. This lets the terminal and HTML renderers share a
+/// single walk over a `Layout`.
+pub trait StyledWrite {
+    /// Start a span of text in a style, nested within the spans that have
+    /// been started already
+    fn start(&mut self, style: Style) -> io::Result<()>;
+    /// End the span that was started most recently
+    fn end(&mut self) -> io::Result<()>;
+    /// Write some text in the current style
+    fn text(&mut self, text: &str) -> io::Result<()>;
+
+    /// Write some text in a style of its own
+    fn styled(&mut self, style: Style, text: &str) -> io::Result<()> {
+        self.start(style)?;
+        self.text(text)?;
+        self.end()
+    }
+}
+
+/// Writes to a terminal, setting the colors of `styles` when text is
+/// written in a style that they have a color for
+struct ColorWriter<'a, W: 'a> {
+    writer: &'a mut W,
+    styles: &'a Styles,
+    /// The color of each of the spans that have been started, or `None` if
+    /// they inherit the color of the span around them
+    stack: Vec<Option<&'a ColorSpec>>,
+    /// The color that the terminal is currently set to
+    current: Option<&'a ColorSpec>,
+}
+
+impl<'a, W: WriteColor> ColorWriter<'a, W> {
+    fn new(writer: &'a mut W, styles: &'a Styles) -> ColorWriter<'a, W> {
+        ColorWriter {
+            writer,
+            styles,
+            stack: Vec::new(),
+            current: None,
+        }
+    }
+}
+
+impl<'a, W: WriteColor> StyledWrite for ColorWriter<'a, W> {
+    fn start(&mut self, style: Style) -> io::Result<()> {
+        let styles = self.styles;
+        let spec = match style {
+            Style::Location | Style::Footer => None,
+            Style::Header | Style::HeaderMessage => Some(&styles.header_message),
+            Style::HeaderSeverity(severity) => Some(styles.header(severity)),
+            Style::Gutter => Some(&styles.gutter),
+            Style::FooterSeverity(severity) => Some(styles.footer(severity)),
+            Style::Label(severity, style) => Some(styles.label(severity, style)),
+        };
+        let inherited = self.stack.last().cloned().unwrap_or(None);
+        self.stack.push(spec.or(inherited));
+        Ok(())
+    }
+
+    fn end(&mut self) -> io::Result<()> {
+        self.stack.pop();
+        // Leave the terminal as we found it once everything has ended
+        if self.stack.is_empty() && self.current.is_some() {
+            self.current = None;
+            self.writer.reset()?;
+        }
+        Ok(())
+    }
+
+    fn text(&mut self, text: &str) -> io::Result<()> {
+        let spec = self.stack.last().cloned().unwrap_or(None);
+        if spec != self.current {
+            match spec {
+                Some(spec) => self.writer.set_color(spec)?,
+                None => self.writer.reset()?,
+            }
+            self.current = spec;
+        }
+        write!(self.writer, "{}", text)
+    }
+}
+
+/// Write a diagnostic and its children, as laid out in `layout`
+pub fn render_layout<W>(
+    writer: &mut W,
+    config: &Config,
+    layout: &Layout,
+    gutter_width: usize,
+) -> io::Result<()>
+where
+    W: StyledWrite,
+{
+    let diagnostic = layout.diagnostic;
+
+    render_header(
+        writer,
+        config,
+        diagnostic.severity,
+        diagnostic.code.as_deref(),
+        &diagnostic.message,
+    )?;
+
+    for part in &layout.parts {
+        match *part {
+            Part::Invalid(label, ref reason) => {
+                let message = invalid_label_note(&config.catalog, label, reason);
+                render_note(writer, config, gutter_width, Severity::Note, &message)?
+            },
+            Part::Snippet(ref snippet) => {
+                render_snippet(writer, config, snippet, diagnostic.severity, gutter_width)?
+            },
+        }
+    }
+
+    for note in &diagnostic.notes {
+        render_note(writer, config, gutter_width, note.severity, &note.message)?;
+    }
 
-    let highlight_color = ColorSpec::new().set_bold(true).set_intense(true).clone();
+    // A hint pointing to the explanation of the code, if it has one:
+    //
+    // ```text
+    //   = help: For more information about this error, try `--explain E0001`.
+    // ```
+    let hint = config
+        .registry
+        .as_ref()
+        .and_then(|registry| registry.hint(&config.catalog, diagnostic.code.as_deref()));
+    if let Some(hint) = hint {
+        render_note(writer, config, gutter_width, Severity::Help, &hint)?;
+    }
 
-    writer.set_color(&highlight_color
-        .clone()
-        .set_fg(Some(diagnostic.severity.color())))?;
-    write!(writer, "{}", diagnostic.severity)?;
+    // Suggestions, showing the source code as it would look once the
+    // suggestion is applied:
+    //
+    // ```text
+    // help: try adding a semicolon
+    // - <test>:3:12
+    // 3 |     foo(bar);
+    //   |             +
+    // ```
+    for &(suggestion, ref snippet) in &layout.suggestions {
+        render_header(
+            writer,
+            config,
+            Severity::Help,
+            None,
+            &suggestion.message,
+        )?;
+        if let Some(ref snippet) = *snippet {
+            render_snippet(writer, config, snippet, Severity::Help, gutter_width)?;
+        }
+    }
 
-    if let Some(ref code) = diagnostic.code {
-        write!(writer, "[{}]", code)?;
+    for child in &layout.children {
+        render_layout(writer, config, child, gutter_width)?;
     }
 
-    writer.set_color(&highlight_color)?;
-    writeln!(writer, ": {}", diagnostic.message)?;
-    writer.reset()?;
+    Ok(())
+}
+
+/// Write a note beneath the snippets of a diagnostic:
+///
+/// ```text
+///   = note: expected type `Int`
+///              found type `String`
+/// ```
+fn render_note<W>(
+    writer: &mut W,
+    config: &Config,
+    gutter_width: usize,
+    severity: Severity,
+    message: &str,
+) -> io::Result<()>
+where
+    W: StyledWrite,
+{
+    let padding = gutter_padding(config, gutter_width);
+    let name = config.catalog.severity(severity);
+
+    writer.start(Style::Footer)?;
+    writer.styled(
+        Style::Gutter,
+        &format!("{}{} ", Pad(' ', padding), config.glyphs.note),
+    )?;
+    writer.styled(Style::FooterSeverity(severity), &format!("{}:", name))?;
+
+    let indent = padding + UnicodeWidthStr::width(name) + 4;
+    for (i, line) in message.lines().enumerate() {
+        match i {
+            0 => writer.text(&format!(" {}", line))?,
+            _ => writer.text(&format!("\n{}{}", Pad(' ', indent), line))?,
+        }
+    }
+    writer.end()?;
+    writer.text("\n")
+}
+
+/// Emit a diagnostic in the short display style, followed by its children
+fn emit_short<W>(
+    writer: &mut W,
+    config: &Config,
+    codemap: &CodeMap,
+    diagnostic: &Diagnostic,
+) -> io::Result<()>
+where
+    W: StyledWrite,
+{
+    let mut has_location = false;
 
     for label in &diagnostic.labels {
-        match codemap.find_file(label.span.start()) {
-            None => if let Some(ref message) = label.message {
-                writeln!(writer, "- {}", message)?
+        let location = match SpanLocation::new(codemap, label.span) {
+            Ok(location) => location,
+            Err(_) => continue,
+        };
+        let (line, column) = location.start;
+
+        let mut position = format!(
+            "{}:{}:{}",
+            location.file.name(),
+            line.number(),
+            column.number()
+        );
+        if config.end_positions {
+            let (line, column) = location.end;
+            position.push_str(&format!("-{}:{}", line.number(), column.number()));
+        }
+        writer.styled(Style::HeaderMessage, &format!("{}: ", position))?;
+
+        let message = match label.message {
+            Some(ref label_message) => format!("{}: {}", diagnostic.message, label_message),
+            None => diagnostic.message.clone(),
+        };
+        render_header(
+            writer,
+            config,
+            diagnostic.severity,
+            diagnostic.code.as_deref(),
+            &message,
+        )?;
+        has_location = true;
+    }
+
+    // Diagnostics without any locations are still shown, so that they are
+    // not lost entirely
+    if !has_location {
+        render_header(
+            writer,
+            config,
+            diagnostic.severity,
+            diagnostic.code.as_deref(),
+            &diagnostic.message,
+        )?;
+    }
+
+    for child in &diagnostic.children {
+        emit_short(writer, config, codemap, child)?;
+    }
+
+    Ok(())
+}
+
+/// The number of columns before the gutter on rows without a line number
+fn gutter_padding(config: &Config, gutter_width: usize) -> usize {
+    if config.show_line_numbers {
+        gutter_width + 1
+    } else {
+        0
+    }
+}
+
+fn render_header<W>(
+    writer: &mut W,
+    config: &Config,
+    severity: Severity,
+    code: Option<&str>,
+    message: &str,
+) -> io::Result<()>
+where
+    W: StyledWrite,
+{
+    let name = config.catalog.severity(severity);
+
+    writer.start(Style::Header)?;
+    match code {
+        Some(code) => writer.styled(
+            Style::HeaderSeverity(severity),
+            &format!("{}[{}]", name, code),
+        )?,
+        None => writer.styled(Style::HeaderSeverity(severity), name)?,
+    }
+    writer.text(": ")?;
+    writer.styled(Style::HeaderMessage, message)?;
+    writer.end()?;
+    writer.text("\n")
+}
+
+fn render_snippet<W>(
+    writer: &mut W,
+    config: &Config,
+    snippet: &Snippet,
+    severity: Severity,
+    gutter_width: usize,
+) -> io::Result<()>
+where
+    W: StyledWrite,
+{
+    let (line, column) = snippet.file.location(snippet.origin).expect("location");
+    writer.styled(
+        Style::Location,
+        &format!(
+            "- {file}:{line}:{column}",
+            file = snippet.file.name(),
+            line = line.number(),
+            column = column.number(),
+        ),
+    )?;
+    writer.text("\n")?;
+
+    for row in &snippet.rows {
+        let segments = match *row {
+            Row::Source(line, ref segments) if config.show_line_numbers => {
+                let gutter = format!(
+                    "{:>width$} {} ",
+                    line.number(),
+                    config.glyphs.gutter,
+                    width = gutter_width,
+                );
+                writer.styled(Style::Gutter, &gutter)?;
+                segments
             },
-            Some(file) => {
-                let (line, column) = file.location(label.span.start()).expect("location");
-                writeln!(
-                    writer,
-                    "- {file}:{line}:{column}",
-                    file = file.name(),
-                    line = line.number(),
-                    column = column.number(),
-                )?;
-
-                let line_span = file.line_span(line).expect("line_span");
-
-                let line_prefix = file.src_slice(line_span.with_end(label.span.start()))
-                    .expect("line_prefix");
-                let line_marked = file.src_slice(label.span).expect("line_marked");
-                let line_suffix = file.src_slice(line_span.with_start(label.span.end()))
-                    .expect("line_suffix")
-                    .trim_right_matches(|ch: char| ch == '\r' || ch == '\n');
-
-                let mark = match label.style {
-                    LabelStyle::Primary => '^',
-                    LabelStyle::Secondary => '-',
-                };
-                let label_color = match label.style {
-                    LabelStyle::Primary => diagnostic_color.clone(),
-                    LabelStyle::Secondary => ColorSpec::new()
-                        .set_fg(Some(if cfg!(windows) {
-                            Color::Cyan
-                        } else {
-                            Color::Blue
-                        }))
-                        .clone(),
-                };
-
-                writer.set_color(&line_location_color)?;
-                let line_string = line.number().to_string();
-                let line_location_prefix = format!("{} | ", Pad(' ', line_string.len()));
-                write!(writer, "{} | ", line_string)?;
-                writer.reset()?;
-
-                write!(writer, "{}", line_prefix)?;
-                writer.set_color(&label_color)?;
-                write!(writer, "{}", line_marked)?;
-                writer.reset()?;
-                writeln!(writer, "{}", line_suffix)?;
-
-                if !supports_color || label.message.is_some() {
-                    writer.set_color(&line_location_color)?;
-                    write!(writer, "{}", line_location_prefix)?;
-                    writer.reset()?;
-
-                    writer.set_color(&label_color)?;
-                    write!(
-                        writer,
-                        "{}{}",
-                        Pad(' ', line_prefix.len()),
-                        Pad(mark, line_marked.len()),
-                    )?;
-                    writer.reset()?;
-
-                    if label.message.is_none() {
-                        writeln!(writer)?;
-                    }
-                }
-
-                match label.message {
-                    None => (),
-                    Some(ref label) => {
-                        writer.set_color(&label_color)?;
-                        writeln!(writer, " {}", label)?;
-                        writer.reset()?;
-                    },
-                }
+            Row::Source(_, ref segments)
+            | Row::Added(ref segments)
+            | Row::Annotation(ref segments) => {
+                let padding = gutter_padding(config, gutter_width);
+                let gutter = format!("{}{} ", Pad(' ', padding), config.glyphs.gutter);
+                writer.styled(Style::Gutter, &gutter)?;
+                segments
             },
+            Row::Elision => {
+                writer.styled(Style::Gutter, "...")?;
+                writer.text("\n")?;
+                continue;
+            },
+        };
+
+        for segment in segments {
+            match segment.style {
+                None => writer.text(&segment.text)?,
+                Some(style) => writer.styled(Style::Label(severity, style), &segment.text)?,
+            }
         }
+        writer.text("\n")?;
     }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use codespan::{ByteOffset, CodeMap, Span};
+    use termcolor::NoColor;
+
+    use super::*;
+    use {Applicability, Diagnostic, Label, Suggestion};
+
+    fn emit_to_string(config: &Config, codemap: &CodeMap, diagnostic: &Diagnostic) -> String {
+        let mut writer = NoColor::new(Vec::new());
+        emit(&mut writer, config, codemap, diagnostic).unwrap();
+        String::from_utf8(writer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn multiline_label_at_line_start() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "(foo\n  bar)\n".to_string());
+        let start = file.byte_index(0.into(), 0.into()).unwrap();
+        let end = file.byte_index(1.into(), 6.into()).unwrap();
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_label(Label::new_primary(Span::new(start, end)).with_message("here"));
+
+        assert_eq!(
+            emit_to_string(&Config::default(), &codemap, &diagnostic),
+            "error: oops\n\
+             - <test>:1:1\n\
+             1 | / (foo\n\
+             2 | |   bar)\n\
+             \x20 | |______^ here\n",
+        );
+    }
+
+    #[test]
+    fn multiline_label_mid_line() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "(foo (bar\n  baz))\n".to_string());
+        let start = file.byte_index(0.into(), 5.into()).unwrap();
+        let end = file.byte_index(1.into(), 6.into()).unwrap();
+        let diagnostic =
+            Diagnostic::new_error("oops").with_label(Label::new_secondary(Span::new(start, end)));
+
+        assert_eq!(
+            emit_to_string(&Config::default(), &codemap, &diagnostic),
+            "error: oops\n\
+             - <test>:1:6\n\
+             1 |   (foo (bar\n\
+             \x20 |  ______-\n\
+             2 | |   baz))\n\
+             \x20 | |______-\n",
+        );
+    }
+
+    #[test]
+    fn multiline_label_ending_after_newline() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "foo\nbar\n".to_string());
+        let start = file.byte_index(0.into(), 0.into()).unwrap();
+        let end = file.byte_index(1.into(), 0.into()).unwrap();
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_label(Label::new_primary(Span::new(start, end)).with_message("here"));
+
+        assert_eq!(
+            emit_to_string(&Config::default(), &codemap, &diagnostic),
+            "error: oops\n\
+             - <test>:1:1\n\
+             1 | foo\n\
+             \x20 | ^^^ here\n",
+        );
+    }
+    #[test]
+    fn labels_on_same_line_share_a_snippet() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "(+ test \"\")\n".to_string());
+        let span = |start: u32, len: i64| {
+            let start = file.byte_index(0.into(), start.into()).unwrap();
+            Span::from_offset(start, len.into())
+        };
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_label(Label::new_secondary(span(1, 1)).with_message("function"))
+            .with_label(Label::new_secondary(span(3, 4)).with_message("argument"))
+            .with_label(Label::new_primary(span(8, 2)).with_message("bad"));
+
+        assert_eq!(
+            emit_to_string(&Config::default(), &codemap, &diagnostic),
+            "error: oops\n\
+             - <test>:1:9\n\
+             1 | (+ test \"\")\n\
+             \x20 |  - ---- ^^ bad\n\
+             \x20 |  | |\n\
+             \x20 |  | argument\n\
+             \x20 |  function\n",
+        );
+    }
+
+    #[test]
+    fn overlapping_labels_are_stacked() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "foo(bar, baz)\n".to_string());
+        let span = |start: u32, len: i64| {
+            let start = file.byte_index(0.into(), start.into()).unwrap();
+            Span::from_offset(start, len.into())
+        };
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_label(Label::new_primary(span(4, 3)).with_message("inner"))
+            .with_label(Label::new_secondary(span(0, 13)).with_message("outer"))
+            .with_label(Label::new_secondary(span(9, 3)));
+
+        assert_eq!(
+            emit_to_string(&Config::default(), &codemap, &diagnostic),
+            "error: oops\n\
+             - <test>:1:5\n\
+             1 | foo(bar, baz)\n\
+             \x20 | ------------- outer\n\
+             \x20 |     ^^^  ---\n\
+             \x20 |     |\n\
+             \x20 |     inner\n",
+        );
+    }
+
+    #[test]
+    fn multiline_and_single_line_labels() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "(foo (bar\n  baz))\n".to_string());
+        let index = |line: u32, column: u32| file.byte_index(line.into(), column.into()).unwrap();
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_label(
+                Label::new_primary(Span::new(index(0, 5), index(1, 6))).with_message("call"),
+            )
+            .with_label(
+                Label::new_secondary(Span::new(index(0, 1), index(0, 4))).with_message("name"),
+            );
+
+        assert_eq!(
+            emit_to_string(&Config::default(), &codemap, &diagnostic),
+            "error: oops\n\
+             - <test>:1:6\n\
+             1 |   (foo (bar\n\
+             \x20 |    --- name\n\
+             \x20 |  ______^\n\
+             2 | |   baz))\n\
+             \x20 | |______^ call\n",
+        );
+    }
+
+    #[test]
+    fn context_lines_are_merged_and_elided() {
+        let mut codemap = CodeMap::new();
+        let source = (1..12).map(|i| format!("line {}\n", i)).collect::<String>();
+        let file = codemap.add_filemap("test".into(), source);
+        let span = |line: u32| {
+            let start = file.byte_index(line.into(), 0.into()).unwrap();
+            Span::from_offset(start, 4.into())
+        };
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_label(Label::new_primary(span(1)))
+            .with_label(Label::new_secondary(span(3)))
+            .with_label(Label::new_secondary(span(9)));
+        let config = Config {
+            context_lines: 1,
+            ..Config::default()
+        };
+
+        let mut writer = NoColor::new(Vec::new());
+        emit(&mut writer, &config, &codemap, &diagnostic).unwrap();
+
+        let expected = r"error: oops
+- <test>:2:1
+ 1 | line 1
+ 2 | line 2
+   | ^^^^
+ 3 | line 3
+ 4 | line 4
+   | ----
+ 5 | line 5
+...
+ 9 | line 9
+10 | line 10
+   | ----
+11 | line 11
+";
+        assert_eq!(String::from_utf8(writer.into_inner()).unwrap(), expected);
+    }
+
+    #[test]
+    fn notes_and_children() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "(define x 1)\n(define x 2)\n".to_string());
+        let span = |line: u32| {
+            let start = file.byte_index(line.into(), 8.into()).unwrap();
+            Span::from_offset(start, 1.into())
+        };
+        let diagnostic = Diagnostic::new_error("`x` is defined more than once")
+            .with_label(Label::new_primary(span(1)).with_message("redefined here"))
+            .with_note("definitions must be unique\nwithin a module")
+            .with_help("try renaming one of the definitions")
+            .with_child(
+                Diagnostic::new_note("previous definition here")
+                    .with_label(Label::new_secondary(span(0))),
+            );
+
+        let expected = r"error: `x` is defined more than once
+- <test>:2:9
+2 | (define x 2)
+  |         ^ redefined here
+  = note: definitions must be unique
+          within a module
+  = help: try renaming one of the definitions
+note: previous definition here
+- <test>:1:9
+1 | (define x 1)
+  |         -
+";
+        assert_eq!(emit_to_string(&Config::default(), &codemap, &diagnostic), expected);
+    }
+
+    #[test]
+    fn explain_hint() {
+        use explain::Registry;
+
+        let codemap = CodeMap::new();
+        let config = Config {
+            registry: Some(Registry::new().with_explanation("E0001", "# Oops")),
+            ..Config::default()
+        };
+
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_code("E0001")
+            .with_note("something went wrong");
+        let expected = r"error[E0001]: oops
+ = note: something went wrong
+ = help: For more information about this error, try `--explain E0001`.
+";
+        assert_eq!(emit_to_string(&config, &codemap, &diagnostic), expected);
+
+        // Codes without an explanation have no hint
+        let diagnostic = Diagnostic::new_error("oops").with_code("E0002");
+        assert_eq!(emit_to_string(&config, &codemap, &diagnostic), "error[E0002]: oops\n");
+    }
+
+    #[test]
+    fn suggestions() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "let x = foo(bar)\n".to_string());
+        let span = |start: u32, len: i64| {
+            let start = file.byte_index(0.into(), start.into()).unwrap();
+            Span::from_offset(start, len.into())
+        };
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_suggestion(Suggestion::new(
+                span(16, 0),
+                "try adding a semicolon",
+                ";",
+                Applicability::MachineApplicable,
+            ))
+            .with_suggestion(Suggestion::new(
+                span(8, 3),
+                "try calling `baz` instead",
+                "baz",
+                Applicability::MaybeIncorrect,
+            ))
+            .with_suggestion(Suggestion::new(
+                span(3, 2),
+                "remove the binding",
+                "",
+                Applicability::HasPlaceholders,
+            ));
+
+        let expected = r"error: oops
+help: try adding a semicolon
+- <test>:1:17
+1 | let x = foo(bar);
+  |                 +
+help: try calling `baz` instead
+- <test>:1:9
+1 | let x = baz(bar)
+  |         ~~~
+help: remove the binding
+- <test>:1:4
+1 | let x = foo(bar)
+  |    --
+";
+        assert_eq!(emit_to_string(&Config::default(), &codemap, &diagnostic), expected);
+    }
+
+    #[test]
+    fn suggestions_are_truncated() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "let x = foo(bar)\n".to_string());
+        let start = file.byte_index(0.into(), 8.into()).unwrap();
+        let span = Span::from_offset(start, 3.into());
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_suggestion(Suggestion::new(
+                span,
+                "try calling `bazbazbaz` instead",
+                "bazbazbaz",
+                Applicability::MaybeIncorrect,
+            ))
+            .with_suggestion(Suggestion::new(
+                span,
+                "remove the call",
+                "",
+                Applicability::MaybeIncorrect,
+            ));
+        let config = Config {
+            max_width: Some(14),
+            ..Config::default()
+        };
+
+        let expected = r"error: oops
+help: try calling `bazbazbaz` instead
+- <test>:1:9
+1 | ...bazb...
+  |    ~~~~
+help: remove the call
+- <test>:1:9
+1 | ...foo(...
+  |    ---
+";
+        assert_eq!(emit_to_string(&config, &codemap, &diagnostic), expected);
+    }
+
+    #[test]
+    fn multiline_suggestions() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "fn main() {\n    run();\n}".to_string());
+        let span = |line: u32, column: u32, len: i64| {
+            let start = file.byte_index(line.into(), column.into()).unwrap();
+            Span::from_offset(start, len.into())
+        };
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_suggestion(Suggestion::new(
+                span(1, 0, 0),
+                "try importing `run`",
+                "    use tasks::run;\n",
+                Applicability::MachineApplicable,
+            ))
+            .with_suggestion(Suggestion::new(
+                span(2, 0, 1),
+                "try returning a result",
+                "    Ok(())\n}",
+                Applicability::MaybeIncorrect,
+            ))
+            .with_suggestion(Suggestion::new(
+                span(1, 0, 0),
+                "add a blank line",
+                "\n",
+                Applicability::MachineApplicable,
+            ))
+            .with_suggestion(Suggestion::new(
+                span(0, 11, 0),
+                "add a blank line",
+                "\n",
+                Applicability::MachineApplicable,
+            ));
+
+        let expected = "error: oops
+help: try importing `run`
+- <test>:2:1
+2 |     use tasks::run;
+  | +++++++++++++++++++
+  |     run();
+help: try returning a result
+- <test>:3:1
+3 |     Ok(())
+  | ~~~~~~~~~~
+  | }
+  | ~
+help: add a blank line
+- <test>:2:1
+2 | \n  | +
+  |     run();
+help: add a blank line
+- <test>:1:12
+1 | fn main() {
+  |            +
+  | \n";
+        assert_eq!(emit_to_string(&Config::default(), &codemap, &diagnostic), expected);
+    }
+
+    #[test]
+    fn short_display_style() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "(define x 1)\n(define x 2)\n".to_string());
+        let span = |line: u32| {
+            let start = file.byte_index(line.into(), 8.into()).unwrap();
+            Span::from_offset(start, 1.into())
+        };
+        let diagnostic = Diagnostic::new_error("`x` is defined more than once")
+            .with_code("E0001")
+            .with_label(Label::new_primary(span(1)).with_message("redefined here"))
+            .with_note("definitions must be unique")
+            .with_child(
+                Diagnostic::new_note("previous definition here")
+                    .with_label(Label::new_secondary(span(0))),
+            )
+            .with_child(Diagnostic::new_help("try renaming one of the definitions"));
+
+        let mut config = Config {
+            display_style: DisplayStyle::Short,
+            ..Config::default()
+        };
+        let emit_to_string = |config: &Config| {
+            let mut writer = NoColor::new(Vec::new());
+            emit(&mut writer, config, &codemap, &diagnostic).unwrap();
+            String::from_utf8(writer.into_inner()).unwrap()
+        };
+
+        assert_eq!(
+            emit_to_string(&config),
+            "<test>:2:9: error[E0001]: `x` is defined more than once: redefined here
+<test>:1:9: note: previous definition here
+help: try renaming one of the definitions
+",
+        );
+
+        config.end_positions = true;
+        assert_eq!(
+            emit_to_string(&config),
+            "<test>:2:9-2:10: error[E0001]: `x` is defined more than once: redefined here
+<test>:1:9-1:10: note: previous definition here
+help: try renaming one of the definitions
+",
+        );
+    }
+
+    #[test]
+    fn tee_emitter() {
+        use json::JsonEmitter;
+
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "foo(bar)\n".to_string());
+        let start = file.byte_index(0.into(), 4.into()).unwrap();
+        let diagnostics = vec![
+            Diagnostic::new_error("unknown variable")
+                .with_label(Label::new_primary(Span::from_offset(start, 3.into()))),
+            Diagnostic::new_warning("unused result"),
+        ];
+
+        let mut term = TermEmitter::new(NoColor::new(Vec::new()), Config::default());
+        let mut json = JsonEmitter::new(Vec::new(), Config::default());
+        // Output that is not written to a terminal is never trimmed
+        assert_eq!(term.config.max_width, None);
+        {
+            let mut tee = TeeEmitter::new()
+                .with_emitter(&mut term)
+                .with_emitter(&mut json);
+            for diagnostic in &diagnostics {
+                tee.emit(&codemap, diagnostic).unwrap();
+            }
+            tee.finish().unwrap();
+        }
+
+        let mut expected = String::new();
+        for diagnostic in &diagnostics {
+            expected.push_str(&emit_to_string(&Config::default(), &codemap, diagnostic));
+        }
+        let term = String::from_utf8(term.writer.into_inner()).unwrap();
+        assert_eq!(term, expected);
+
+        let json = String::from_utf8(json.writer).unwrap();
+        assert_eq!(json.lines().count(), 2);
+        assert!(json.contains("\"message\":\"unused result\""));
+    }
+
+    #[test]
+    fn tabs_are_expanded() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "\tfoo\t(bar)\n".to_string());
+        let start = file.byte_index(0.into(), 6.into()).unwrap();
+        let diagnostic = Diagnostic::new_error("oops").with_label(
+            Label::new_primary(Span::from_offset(start, 3.into())).with_message("here"),
+        );
+
+        let config = Config {
+            tab_width: 4,
+            ..Config::default()
+        };
+        let expected = r"error: oops
+- <test>:1:7
+1 |     foo (bar)
+  |          ^^^ here
+";
+        assert_eq!(emit_to_string(&config, &codemap, &diagnostic), expected);
+        let config = Config {
+            tab_width: 2,
+            ..Config::default()
+        };
+        let expected = r"error: oops
+- <test>:1:7
+1 |   foo (bar)
+  |        ^^^ here
+";
+        assert_eq!(emit_to_string(&config, &codemap, &diagnostic), expected);
+        let config = Config {
+            tab_width: 0,
+            ..Config::default()
+        };
+        let expected = r"error: oops
+- <test>:1:7
+1 |  foo (bar)
+  |       ^^^ here
+";
+        assert_eq!(emit_to_string(&config, &codemap, &diagnostic), expected);
+    }
+
+    #[test]
+    fn glyphs_and_line_numbers() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap(
+            "test".into(),
+            "  (define (foo x)\n    (bar x))\n".to_string(),
+        );
+        let start = file.byte_index(0.into(), 10.into()).unwrap();
+        let end = file.byte_index(1.into(), 12.into()).unwrap();
+        let mid = file.byte_index(1.into(), 5.into()).unwrap();
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_label(Label::new_primary(Span::new(start, end)).with_message("here"))
+            .with_label(
+                Label::new_secondary(Span::from_offset(mid, 3.into())).with_message("there"),
+            )
+            .with_note("a note");
+
+        let config = Config {
+            glyphs: Glyphs::unicode(),
+            ..Config::default()
+        };
+        let expected = r"error: oops
+- <test>:1:11
+1 │     (define (foo x)
+  │ ╭───────────━
+2 │ │     (bar x))
+  │ │      ─── there
+  │ ╰────────────━ here
+  = note: a note
+";
+        assert_eq!(emit_to_string(&config, &codemap, &diagnostic), expected);
+
+        let config = Config {
+            show_line_numbers: false,
+            ..Config::default()
+        };
+        let expected = r"error: oops
+- <test>:1:11
+|     (define (foo x)
+|  ___________^
+| |     (bar x))
+| |      --- there
+| |____________^ here
+= note: a note
+";
+        assert_eq!(emit_to_string(&config, &codemap, &diagnostic), expected);
+    }
+
+    #[test]
+    fn custom_styles() {
+        use termcolor::Ansi;
+
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "foo(bar)\n".to_string());
+        let start = file.byte_index(0.into(), 4.into()).unwrap();
+        let diagnostic = Diagnostic::new_warning("unused")
+            .with_label(Label::new_primary(Span::from_offset(start, 3.into())));
+
+        let config = Config {
+            styles: "header-warning=35:header-message=0:primary-label-warning=4;35:gutter=0"
+                .parse()
+                .unwrap(),
+            ..Config::default()
+        };
+        let mut writer = Ansi::new(Vec::new());
+        emit(&mut writer, &config, &codemap, &diagnostic).unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+
+        assert!(output.starts_with("\u{1b}[0m\u{1b}[35mwarning\u{1b}[0m: unused"));
+        assert!(output.contains("\u{1b}[0m\u{1b}[4m\u{1b}[35mbar"));
+    }
+
+    #[test]
+    fn localized() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "let x = y;\n".to_string());
+        let start = file.byte_index(0.into(), 8.into()).unwrap();
+        let diagnostic = Diagnostic::new_error("unknown variable `y`")
+            .with_message_id("unknown-variable")
+            .with_arg("name", "y")
+            .with_label(
+                Label::new_primary(Span::from_offset(start, 1.into()))
+                    .with_message("not found")
+                    .with_message_id("not-found"),
+            )
+            .with_label(Label::new_secondary(Span::from_offset(start, 99.into())))
+            .with_help("untranslated");
+
+        let catalog = Catalog::parse(
+            "de",
+            "severity-error = Fehler\n\
+             severity-note = Hinweis\n\
+             severity-help = Hilfe\n\
+             unknown-variable = unbekannte Variable `{ $name }`\n\
+             not-found = nicht gefunden\n\
+             invalid-label = Markierung nicht anzeigbar:\n    { $reason }\n",
+        )
+        .unwrap();
+        let config = Config {
+            catalog,
+            ..Config::default()
+        };
+
+        assert_eq!(
+            emit_to_string(&config, &codemap, &diagnostic),
+            "\
+Fehler: unbekannte Variable `y`
+  = Hinweis: Markierung nicht anzeigbar:
+             the span 9..108 extends past the end of <test>
+- <test>:1:9
+1 | let x = y;
+  |         ^ nicht gefunden
+  = Hilfe: untranslated
+",
+        );
+    }
+
+    #[test]
+    fn localized_notes_are_aligned() {
+        let codemap = CodeMap::new();
+        let diagnostic = Diagnostic::new_error("oops").with_note("expected `Int`\n   found `String`");
+        let config = Config {
+            catalog: Catalog::new("zh").with_message("severity-note", "注意"),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            emit_to_string(&config, &codemap, &diagnostic),
+            "\
+error: oops
+ = 注意: expected `Int`
+            found `String`
+",
+        );
+    }
+
+    #[test]
+    fn wide_characters() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "hi萤 = \"萤火虫\"\n\thi萤\n".to_string());
+        let span = |line: u32, start: u32, end: u32| {
+            let start = file.byte_index(line.into(), start.into()).unwrap();
+            let end = file.byte_index(line.into(), end.into()).unwrap();
+            Span::new(start, end)
+        };
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_label(Label::new_primary(span(0, 2, 5)).with_message("wide"))
+            .with_label(Label::new_secondary(span(0, 8, 19)).with_message("wider"))
+            .with_label(Label::new_secondary(span(1, 1, 6)).with_message("tab"));
+
+        let expected = r#"error: oops
+- <test>:1:3
+1 | hi萤 = "萤火虫"
+  |   ^^   -------- wider
+  |   |
+  |   wide
+2 |     hi萤
+  |     ---- tab
+"#;
+        assert_eq!(emit_to_string(&Config::default(), &codemap, &diagnostic), expected);
+    }
+
+    #[test]
+    fn combining_marks() {
+        let mut codemap = CodeMap::new();
+        // The `é`s are written as an `e` followed by a combining acute accent
+        let file = codemap.add_filemap("test".into(), "cafe\u{301} = ne\u{301}e\n".to_string());
+        let span = |start: u32, end: u32| {
+            let start = file.byte_index(0.into(), start.into()).unwrap();
+            let end = file.byte_index(0.into(), end.into()).unwrap();
+            Span::new(start, end)
+        };
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_label(Label::new_primary(span(0, 6)).with_message("here"))
+            .with_label(Label::new_secondary(span(9, 14)));
+
+        let expected = "error: oops
+- <test>:1:1
+1 | cafe\u{301} = ne\u{301}e
+  | ^^^^   ---
+  | |
+  | here
+";
+        assert_eq!(emit_to_string(&Config::default(), &codemap, &diagnostic), expected);
+    }
+
+    #[test]
+    fn invalid_spans() {
+        let mut codemap = CodeMap::new();
+        let first = codemap.add_filemap("first".into(), "let 萤 = 1;\n".to_string());
+        codemap.add_filemap("second".into(), "let y = 2;\n".to_string());
+        let span = |start: i64, end: i64| {
+            let base = first.span().start();
+            Span::new(base + ByteOffset(start), base + ByteOffset(end))
+        };
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_label(Label::new_primary(span(4, 30)).with_message("crosses files"))
+            .with_label(Label::new_secondary(span(5, 7)))
+            .with_label(Label::new_secondary(span(0, 3)).with_message("fine"))
+            .with_label(Label::new_secondary(span(100, 105)).with_message("stale"))
+            .with_label(Label::new_secondary(span(200, 200)))
+            .with_suggestion(Suggestion::new(
+                span(5, 6),
+                "try this",
+                "x",
+                Applicability::MachineApplicable,
+            ));
+
+        let expected = "\
+error: oops
+  = note: could not show the label `crosses files`: the span 5..31 extends past the end of <first>
+  = note: could not show a label: the span 6..8 is not on a character boundary
+  = note: could not show the label `stale`: the span 101..106 is not in the codemap
+  = note: could not show a label: the span 201..201 is not in the codemap
+- <first>:1:1
+1 | let 萤 = 1;
+  | --- fine
+help: try this
+";
+        assert_eq!(emit_to_string(&Config::default(), &codemap, &diagnostic), expected);
+
+        let config = Config {
+            display_style: DisplayStyle::Short,
+            ..Config::default()
+        };
+        assert_eq!(
+            emit_to_string(&config, &codemap, &diagnostic),
+            "<first>:1:1: error: oops: fine\n",
+        );
+    }
+
+    #[test]
+    fn long_lines_are_truncated() {
+        let src = format!(
+            "let x = [{}];\nfoo(1,\n    {}bar);\n",
+            "0, ".repeat(30),
+            "a".repeat(50),
+        );
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), src.clone());
+        let offset = |line: u32, column: usize| {
+            file.byte_index(line.into(), (column as u32).into())
+                .unwrap()
+        };
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_label(
+                Label::new_primary(Span::new(offset(0, 54), offset(0, 55)))
+                    .with_message("not a number"),
+            )
+            .with_label(
+                Label::new_secondary(Span::new(offset(1, 0), offset(2, 58))).with_message("call"),
+            );
+
+        let config = Config {
+            max_width: Some(40),
+            ..Config::default()
+        };
+        let expected = r"error: oops
+- <test>:1:55
+1 |   ... 0, 0, 0, 0, 0, 0, 0, 0, 0, ...
+  |                   ^ not a number
+2 | / foo(1,
+3 | | ...aaaaaaaaaaaaaaaaaaaaaaabar);
+  | |______________________________- call
+";
+        assert_eq!(emit_to_string(&config, &codemap, &diagnostic), expected);
+    }
+
+    #[test]
+    fn wide_labels_are_clipped() {
+        let mut codemap = CodeMap::new();
+        let src = format!("{}abcd\n", "0123456789".repeat(4));
+        let file = codemap.add_filemap("test".into(), src);
+        let span = |start: u32, end: u32| {
+            let start = file.byte_index(0.into(), start.into()).unwrap();
+            let end = file.byte_index(0.into(), end.into()).unwrap();
+            Span::new(start, end)
+        };
+        let config = Config {
+            max_width: Some(20),
+            ..Config::default()
+        };
+
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_label(Label::new_primary(span(0, 44)).with_message("everything"));
+        let expected = r"error: oops
+- <test>:1:1
+1 | 0123456789...
+  | ^^^^^^^^^^ everything
+";
+        assert_eq!(emit_to_string(&config, &codemap, &diagnostic), expected);
+
+        // The window is centered on the primary label, and labels outside of
+        // it are not drawn
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_label(Label::new_primary(span(10, 12)).with_message("here"))
+            .with_label(Label::new_secondary(span(41, 44)).with_message("hidden"));
+        let expected = r"error: oops
+- <test>:1:11
+1 | ...6789012345...
+  |        ^^ here
+";
+        assert_eq!(emit_to_string(&config, &codemap, &diagnostic), expected);
+    }
+}
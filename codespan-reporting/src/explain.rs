@@ -0,0 +1,275 @@
+//! Long-form explanations of diagnostic codes, as shown by `--explain`
+
+use std::collections::HashMap;
+use std::io;
+use termcolor::WriteColor;
+
+use locale::Catalog;
+use styles::Styles;
+
+/// The explanations of the codes that diagnostics can have, written in
+/// markdown
+///
+/// When a registry is set in `Config::registry`, diagnostics with a code
+/// that has an explanation end with a hint pointing to it. A
+/// `DiagnosticSink` that has been given a registry with `with_registry` adds
+/// the hint to its summary instead.
+///
+/// ```rust
+/// use codespan_reporting::Registry;
+///
+/// let registry = Registry::new()
+///     .with_explanation("E0001", "# Type mismatch\n\nThe `+` operator only works on integers.")
+///     .with_command("mylang --explain");
+///
+/// assert!(registry.explanation("E0001").is_some());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Registry {
+    explanations: HashMap<String, String>,
+    /// The command that shows an explanation when given a code, as
+    /// suggested in the hints pointing to explanations
+    pub command: String,
+}
+
+impl Default for Registry {
+    fn default() -> Registry {
+        Registry {
+            explanations: HashMap::new(),
+            command: "--explain".to_owned(),
+        }
+    }
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    pub fn with_explanation<S, T>(mut self, code: S, markdown: T) -> Registry
+    where
+        S: Into<String>,
+        T: Into<String>,
+    {
+        self.add(code, markdown);
+        self
+    }
+
+    pub fn with_command<S: Into<String>>(mut self, command: S) -> Registry {
+        self.command = command.into();
+        self
+    }
+
+    /// Add the explanation of a code, replacing any previous explanation
+    pub fn add<S, T>(&mut self, code: S, markdown: T)
+    where
+        S: Into<String>,
+        T: Into<String>,
+    {
+        self.explanations.insert(code.into(), markdown.into());
+    }
+
+    /// The explanation of a code, if it has one
+    pub fn explanation(&self, code: &str) -> Option<&str> {
+        self.explanations.get(code).map(String::as_str)
+    }
+
+    /// The codes that have explanations, in sorted order
+    pub fn codes(&self) -> Vec<&str> {
+        let mut codes = self
+            .explanations
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        codes.sort();
+        codes
+    }
+
+    /// A hint pointing to the explanations of the given codes, or `None` if
+    /// none of them have explanations, using the messages in `catalog`:
+    ///
+    /// ```text
+    /// For more information about this error, try `--explain E0001`.
+    /// ```
+    pub fn hint<'a, I>(&self, catalog: &Catalog, codes: I) -> Option<String>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut explained: Vec<&str> = Vec::new();
+        for code in codes {
+            if self.explanations.contains_key(code) && !explained.contains(&code) {
+                explained.push(code);
+            }
+        }
+
+        let first = *explained.first()?;
+        let args = [
+            ("command".to_owned(), self.command.clone()),
+            ("code".to_owned(), first.to_owned()),
+            ("codes".to_owned(), explained.join(", ")),
+        ];
+        Some(catalog.format_count("explain-hint", explained.len(), &args))
+    }
+}
+
+/// Write an explanation to the terminal, with basic markdown styling.
+///
+/// Headings, fenced code blocks, `code`, and **emphasised** text are styled
+/// using `styles`. Everything else is written as it is.
+pub fn emit_explanation<W>(mut writer: W, styles: &Styles, markdown: &str) -> io::Result<()>
+where
+    W: WriteColor,
+{
+    let mut in_code_block = false;
+
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+
+        if in_code_block {
+            writer.set_color(&styles.code)?;
+            write!(writer, "    {}", line)?;
+            writer.reset()?;
+        } else {
+            let heading = line.trim_start_matches('#');
+            if heading.len() < line.len() && (heading.is_empty() || heading.starts_with(' ')) {
+                writer.set_color(&styles.heading)?;
+                write!(writer, "{}", heading.trim())?;
+                writer.reset()?;
+            } else {
+                emit_inline(&mut writer, styles, line)?;
+            }
+        }
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+/// Write a line of markdown text, styling code spans and emphasis, and
+/// removing their delimiters
+fn emit_inline<W>(writer: &mut W, styles: &Styles, text: &str) -> io::Result<()>
+where
+    W: WriteColor,
+{
+    const DELIMITERS: &[&str] = &["`", "**", "__", "*", "_"];
+
+    let mut plain_start = 0;
+    let mut i = 0;
+    while let Some(ch) = text[i..].chars().next() {
+        let delimiter = DELIMITERS
+            .iter()
+            .find(|delimiter| text[i..].starts_with(**delimiter));
+
+        if let Some(&delimiter) = delimiter {
+            let start = i + delimiter.len();
+            // Emphasis must start at the beginning of a word, so that the
+            // underscores in `snake_case` and the `*` in `a * b` are left alone
+            let can_open = text[start..]
+                .chars()
+                .next()
+                .is_some_and(|ch| !ch.is_whitespace())
+                && (delimiter == "`"
+                    || !text[..i]
+                        .chars()
+                        .next_back()
+                        .is_some_and(char::is_alphanumeric));
+            let len = text[start..].find(delimiter).filter(|&len| len > 0);
+
+            if let (true, Some(len)) = (can_open, len) {
+                write!(writer, "{}", &text[plain_start..i])?;
+                writer.set_color(match delimiter {
+                    "`" => &styles.code,
+                    _ => &styles.emphasis,
+                })?;
+                write!(writer, "{}", &text[start..start + len])?;
+                writer.reset()?;
+
+                i = start + len + delimiter.len();
+                plain_start = i;
+                continue;
+            }
+        }
+
+        i += ch.len_utf8();
+    }
+
+    write!(writer, "{}", &text[plain_start..])
+}
+
+#[cfg(test)]
+mod tests {
+    use termcolor::{Ansi, NoColor};
+
+    use super::*;
+
+    #[test]
+    fn hint() {
+        let registry = Registry::new()
+            .with_explanation("E0001", "")
+            .with_explanation("E0002", "");
+        let catalog = Catalog::english();
+
+        assert_eq!(registry.hint(&catalog, vec!["E0003"]), None);
+        assert_eq!(
+            registry.hint(&catalog, vec!["E0003", "E0001", "E0001"]),
+            Some("For more information about this error, try `--explain E0001`.".to_owned()),
+        );
+        assert_eq!(
+            registry
+                .with_command("lang --explain")
+                .hint(&catalog, vec!["E0002", "E0001"]),
+            Some(
+                "Some errors have detailed explanations: E0002, E0001.\n\
+                 For more information about an error, try `lang --explain E0002`."
+                    .to_owned()
+            ),
+        );
+    }
+
+    const EXPLANATION: &str = "\
+# Type mismatch
+
+The `+` operator expects **integers**, but my_value is a *string*:
+
+```
+(+ 1 \"two\")
+```
+
+Use `string->int` first.
+";
+
+    #[test]
+    fn plain_explanation() {
+        let mut writer = NoColor::new(Vec::new());
+        emit_explanation(&mut writer, &Styles::default(), EXPLANATION).unwrap();
+
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "\
+Type mismatch
+
+The + operator expects integers, but my_value is a string:
+
+    (+ 1 \"two\")
+
+Use string->int first.
+",
+        );
+    }
+
+    #[test]
+    fn styled_explanation() {
+        let styles = "heading=4:code=33:emphasis=1".parse::<Styles>().unwrap();
+        let mut writer = Ansi::new(Vec::new());
+        emit_explanation(&mut writer, &styles, "## Note\nsee `x` and _this_\n").unwrap();
+
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "\u{1b}[0m\u{1b}[4mNote\u{1b}[0m\n\
+             see \u{1b}[0m\u{1b}[33mx\u{1b}[0m and \u{1b}[0m\u{1b}[1mthis\u{1b}[0m\n",
+        );
+    }
+}
@@ -0,0 +1,311 @@
+//! Automatic application of machine-applicable suggestions
+//!
+//! This collects the suggestions marked as `Applicability::MachineApplicable`
+//! from a batch of diagnostics, and applies them to the files in a `CodeMap`.
+//! Suggestions that overlap with a suggestion that has already been applied
+//! are reported as conflicts rather than being applied.
+
+use codespan::{CodeMap, FileMap, FileName, Span};
+use std::sync::Arc;
+use std::{fmt, fs, io};
+
+use emitter::find_file;
+use {Applicability, Diagnostic, Label, Suggestion};
+
+/// A suggestion that could not be applied
+#[derive(Clone, Debug)]
+pub enum Conflict {
+    /// The suggestion overlaps with a suggestion that was already applied
+    Overlapping {
+        suggestion: Suggestion,
+        applied: Suggestion,
+    },
+    /// The span of the suggestion does not lie within a single file of the
+    /// `CodeMap`, or is not on character boundaries
+    InvalidSpan(Suggestion),
+}
+
+impl Conflict {
+    /// The suggestion that could not be applied
+    pub fn suggestion(&self) -> &Suggestion {
+        match *self {
+            Conflict::Overlapping { ref suggestion, .. }
+            | Conflict::InvalidSpan(ref suggestion) => suggestion,
+        }
+    }
+
+    /// Create a warning that can be emitted to report the conflict
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match *self {
+            Conflict::Overlapping {
+                ref suggestion,
+                ref applied,
+            } => Diagnostic::new_warning(format!(
+                "could not apply suggestion `{}`",
+                suggestion.message,
+            ))
+            .with_label(
+                Label::new_primary(suggestion.span)
+                    .with_message("this suggestion overlaps with another"),
+            )
+            .with_label(
+                Label::new_secondary(applied.span)
+                    .with_message(format!("`{}` was applied here", applied.message)),
+            ),
+            Conflict::InvalidSpan(ref suggestion) => Diagnostic::new_warning(format!(
+                "could not apply suggestion `{}`: the span {} is not within a single file",
+                suggestion.message, suggestion.span,
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Conflict::Overlapping {
+                ref suggestion,
+                ref applied,
+            } => write!(
+                f,
+                "suggestion `{}` at {} overlaps with `{}` at {}",
+                suggestion.message, suggestion.span, applied.message, applied.span,
+            ),
+            Conflict::InvalidSpan(ref suggestion) => write!(
+                f,
+                "suggestion `{}` at {} is not within a single file",
+                suggestion.message, suggestion.span,
+            ),
+        }
+    }
+}
+
+/// The result of applying suggestions to a single file
+#[derive(Clone, Debug)]
+pub struct FixedFile {
+    /// The file that the suggestions were applied to
+    pub file: Arc<FileMap>,
+    /// The source code of the file with the suggestions applied
+    pub src: String,
+    /// The suggestions that were applied, in the order they appear in the file
+    pub applied: Vec<Suggestion>,
+}
+
+impl FixedFile {
+    /// Write the fixed source code back to disk, if the file came from disk.
+    ///
+    /// Returns `false` if the file is virtual and was not written.
+    pub fn write_to_disk(&self) -> io::Result<bool> {
+        match *self.file.name() {
+            FileName::Real(ref path) => fs::write(path, &self.src).map(|()| true),
+            FileName::Virtual(_) => Ok(false),
+        }
+    }
+}
+
+/// The result of applying suggestions to the files of a `CodeMap`
+#[derive(Clone, Debug, Default)]
+pub struct Fixes {
+    /// The files that had suggestions applied to them
+    pub files: Vec<FixedFile>,
+    /// The suggestions that could not be applied
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Fixes {
+    /// Write the fixed source code of every file that came from disk
+    pub fn write_to_disk(&self) -> io::Result<()> {
+        for file in &self.files {
+            file.write_to_disk()?;
+        }
+        Ok(())
+    }
+}
+
+/// Apply the machine-applicable suggestions of the given diagnostics, and
+/// their children, to the files in `codemap`.
+///
+/// Suggestions are applied in order of their position in the file, so the
+/// result does not depend on the order of the diagnostics. Identical
+/// suggestions are only applied once.
+pub fn apply_suggestions<'a, Diagnostics>(codemap: &CodeMap, diagnostics: Diagnostics) -> Fixes
+where
+    Diagnostics: IntoIterator<Item = &'a Diagnostic>,
+{
+    fn collect<'a>(diagnostic: &'a Diagnostic, suggestions: &mut Vec<&'a Suggestion>) {
+        suggestions.extend(
+            diagnostic
+                .suggestions
+                .iter()
+                .filter(|suggestion| suggestion.applicability == Applicability::MachineApplicable),
+        );
+        for child in &diagnostic.children {
+            collect(child, suggestions);
+        }
+    }
+
+    let mut suggestions = Vec::new();
+    for diagnostic in diagnostics {
+        collect(diagnostic, &mut suggestions);
+    }
+
+    let mut fixes = Fixes::default();
+    let mut groups: Vec<(&Arc<FileMap>, Vec<&Suggestion>)> = Vec::new();
+
+    for suggestion in suggestions {
+        let file = match find_file(codemap, suggestion.span) {
+            Ok(file) => file,
+            Err(_) => {
+                fixes
+                    .conflicts
+                    .push(Conflict::InvalidSpan(suggestion.clone()));
+                continue;
+            },
+        };
+
+        match groups
+            .iter()
+            .position(|&(group_file, _)| Arc::ptr_eq(group_file, file))
+        {
+            Some(i) => groups[i].1.push(suggestion),
+            None => groups.push((file, vec![suggestion])),
+        }
+    }
+
+    for (file, mut suggestions) in groups {
+        // The sort is stable, so suggestions at the same location are kept
+        // in the order that they were given
+        suggestions.sort_by_key(|suggestion| (suggestion.span.start(), suggestion.span.end()));
+
+        let mut src = String::with_capacity(file.src().len());
+        let mut last = file.span().start();
+        let mut applied: Vec<Suggestion> = Vec::new();
+
+        for suggestion in suggestions {
+            if let Some(previous) = applied.last() {
+                if previous.span == suggestion.span
+                    && previous.replacement == suggestion.replacement
+                {
+                    continue;
+                }
+
+                let is_overlapping = suggestion.span.start() < previous.span.end()
+                    || (suggestion.span == previous.span
+                        && suggestion.span.start() == suggestion.span.end());
+                if is_overlapping {
+                    fixes.conflicts.push(Conflict::Overlapping {
+                        suggestion: suggestion.clone(),
+                        applied: previous.clone(),
+                    });
+                    continue;
+                }
+            }
+
+            src.push_str(
+                file.src_slice(Span::new(last, suggestion.span.start()))
+                    .expect("src_slice"),
+            );
+            src.push_str(&suggestion.replacement);
+            last = suggestion.span.end();
+            applied.push(suggestion.clone());
+        }
+
+        src.push_str(
+            file.src_slice(Span::new(last, file.span().end()))
+                .expect("src_slice"),
+        );
+
+        fixes.files.push(FixedFile {
+            file: file.clone(),
+            src,
+            applied,
+        });
+    }
+
+    fixes
+}
+
+#[cfg(test)]
+mod tests {
+    use codespan::{ByteSpan, CodeMap, Span};
+    use std::env;
+
+    use super::*;
+
+    fn suggestion(span: ByteSpan, replacement: &str) -> Suggestion {
+        Suggestion::new(span, "fix", replacement, Applicability::MachineApplicable)
+    }
+
+    #[test]
+    fn apply_to_multiple_files() {
+        let mut codemap = CodeMap::new();
+        let a = codemap.add_filemap("a".into(), "let x = 1\nlet y = 2\n".into());
+        let b = codemap.add_filemap("b".into(), "foo(bar)".into());
+
+        let a_index = |line: u32, column: u32| a.byte_index(line.into(), column.into()).unwrap();
+        let b_index = |column: u32| b.byte_index(0.into(), column.into()).unwrap();
+
+        let diagnostics = vec![
+            Diagnostic::new_error("missing semicolons")
+                .with_suggestion(suggestion(Span::new(a_index(1, 9), a_index(1, 9)), ";"))
+                .with_suggestion(suggestion(Span::new(a_index(0, 9), a_index(0, 9)), ";")),
+            Diagnostic::new_error("unknown function").with_child(
+                Diagnostic::new_help("did you mean `baz`?")
+                    .with_suggestion(suggestion(Span::new(b_index(0), b_index(3)), "baz")),
+            ),
+            Diagnostic::new_warning("unused variable").with_suggestion(Suggestion::new(
+                Span::new(a_index(0, 4), a_index(0, 5)),
+                "prefix with an underscore",
+                "_x",
+                Applicability::MaybeIncorrect,
+            )),
+        ];
+
+        let fixes = apply_suggestions(&codemap, &diagnostics);
+
+        assert!(fixes.conflicts.is_empty());
+        assert_eq!(fixes.files.len(), 2);
+        assert_eq!(fixes.files[0].src, "let x = 1;\nlet y = 2;\n");
+        assert_eq!(fixes.files[0].applied.len(), 2);
+        assert_eq!(fixes.files[1].src, "baz(bar)");
+    }
+
+    #[test]
+    fn overlapping_suggestions_conflict() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "foo(bar)".into());
+        let index = |column: u32| file.byte_index(0.into(), column.into()).unwrap();
+
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_suggestion(suggestion(Span::new(index(0), index(8)), "baz()"))
+            .with_suggestion(suggestion(Span::new(index(4), index(7)), "qux"))
+            .with_suggestion(suggestion(Span::new(index(0), index(8)), "baz()"))
+            .with_suggestion(suggestion(Span::new(index(8), index(8)), ";"))
+            .with_suggestion(suggestion(Span::new(index(8), index(8)), "!"));
+
+        let fixes = apply_suggestions(&codemap, Some(&diagnostic));
+
+        assert_eq!(fixes.files[0].src, "baz();");
+        assert_eq!(fixes.conflicts.len(), 2);
+        assert_eq!(fixes.conflicts[0].suggestion().replacement, "qux");
+        assert_eq!(fixes.conflicts[1].suggestion().replacement, "!");
+    }
+
+    #[test]
+    fn write_to_disk() {
+        let path = env::temp_dir().join("codespan_reporting_fix_write_to_disk.txt");
+        fs::write(&path, "hello world").unwrap();
+
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap_from_disk(path.clone()).unwrap();
+        let start = file.byte_index(0.into(), 6.into()).unwrap();
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_suggestion(suggestion(Span::from_offset(start, 5.into()), "there"));
+
+        let fixes = apply_suggestions(&codemap, Some(&diagnostic));
+        fixes.write_to_disk().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hello there");
+        fs::remove_file(&path).unwrap();
+    }
+}
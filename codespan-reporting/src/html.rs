@@ -0,0 +1,222 @@
+//! Rendering of diagnostics as HTML
+//!
+//! Diagnostics are laid out in exactly the same way as they are by `emit`,
+//! and written as a `<pre>` element, so the text can be selected and copied.
+//! The parts of the diagnostic are wrapped in `<span>`s with CSS classes,
+//! which can be styled with `HTML_STYLESHEET` or a custom stylesheet:
+//!
+//! | Class                                     | Element                                  |
+//! |-------------------------------------------|------------------------------------------|
+//! | `diagnostic`                              | The `<pre>` containing the diagnostic    |
+//! | `header`                                  | The header of a diagnostic or suggestion |
+//! | `severity`                                | The severity and code in a header        |
+//! | `message`                                 | The message in a header                  |
+//! | `location`                                | The file location above each snippet     |
+//! | `gutter`                                  | Line numbers, and the gutter beside them |
+//! | `footer`                                  | A note or help message in a footer       |
+//! | `label`                                   | Underlines and label messages            |
+//! | `primary`, `secondary`                    | The style of a label                     |
+//! | `bug`, `error`, `warning`, `note`, `help` | The severity of a header or label        |
+
+use codespan::CodeMap;
+use std::{fmt, io};
+
+use emitter::{render_layout, Config, Emitter, Layout, Style, StyledWrite};
+use {Diagnostic, LabelStyle, Severity};
+
+/// A stylesheet for rendered diagnostics, using the same colors as `emit`
+pub const HTML_STYLESHEET: &str = "\
+.diagnostic .header, .diagnostic .severity { font-weight: bold; }
+.diagnostic .location, .diagnostic .gutter, .diagnostic .label.secondary { color: #3b5bdb; }
+.diagnostic .bug, .diagnostic .error { color: #e03131; }
+.diagnostic .warning { color: #f08c00; }
+.diagnostic .note { color: #2f9e44; }
+.diagnostic .help { color: #1098ad; }
+";
+
+/// Text with the characters that are special in HTML escaped
+struct Escape<'a>(&'a str);
+
+impl<'a> fmt::Display for Escape<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut last = 0;
+        for (i, ch) in self.0.char_indices() {
+            let escaped = match ch {
+                '&' => "&amp;",
+                '<' => "&lt;",
+                '>' => "&gt;",
+                '"' => "&quot;",
+                '\'' => "&#39;",
+                _ => continue,
+            };
+            f.write_str(&self.0[last..i])?;
+            f.write_str(escaped)?;
+            last = i + ch.len_utf8();
+        }
+        f.write_str(&self.0[last..])
+    }
+}
+
+/// The CSS class used for a severity
+fn severity_class(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Bug => "bug",
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+        Severity::Help => "help",
+    }
+}
+
+/// Write a diagnostic as an HTML `<pre>` element, laid out as it would be by
+/// `emit` with the given `config`.
+///
+/// The rich display style is always used.
+pub fn emit_html<W>(
+    mut writer: W,
+    config: &Config,
+    codemap: &CodeMap,
+    diagnostic: &Diagnostic,
+) -> io::Result<()>
+where
+    W: io::Write,
+{
+    let diagnostic = &config.catalog.localize(diagnostic);
+    let layout = Layout::new(config, codemap, diagnostic);
+    let gutter_width = layout.gutter_width(config);
+
+    write!(writer, "<pre class=\"diagnostic\">")?;
+    let mut html = HtmlWriter {
+        writer: &mut writer,
+    };
+    render_layout(&mut html, config, &layout, gutter_width)?;
+    writeln!(writer, "</pre>")
+}
+
+/// Writes each diagnostic as an HTML `<pre>` element, using `emit_html`
+#[derive(Clone, Debug)]
+pub struct HtmlEmitter<W> {
+    pub writer: W,
+    pub config: Config,
+}
+
+impl<W: io::Write> HtmlEmitter<W> {
+    pub fn new(writer: W, config: Config) -> HtmlEmitter<W> {
+        HtmlEmitter { writer, config }
+    }
+}
+
+impl<W: io::Write> Emitter for HtmlEmitter<W> {
+    fn emit(&mut self, codemap: &CodeMap, diagnostic: &Diagnostic) -> io::Result<()> {
+        emit_html(&mut self.writer, &self.config, codemap, diagnostic)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Writes styled text as nested `<span>`s with CSS classes, escaping the
+/// text
+struct HtmlWriter<W> {
+    writer: W,
+}
+
+impl<W: io::Write> StyledWrite for HtmlWriter<W> {
+    fn start(&mut self, style: Style) -> io::Result<()> {
+        let class = match style {
+            Style::Header => "header".to_owned(),
+            Style::HeaderSeverity(severity) | Style::FooterSeverity(severity) => {
+                format!("severity {}", severity_class(severity))
+            },
+            Style::HeaderMessage => "message".to_owned(),
+            Style::Location => "location".to_owned(),
+            Style::Gutter => "gutter".to_owned(),
+            Style::Footer => "footer".to_owned(),
+            Style::Label(severity, LabelStyle::Primary) => {
+                format!("label primary {}", severity_class(severity))
+            },
+            Style::Label(_, LabelStyle::Secondary) => "label secondary".to_owned(),
+        };
+        write!(self.writer, "<span class=\"{}\">", class)
+    }
+
+    fn end(&mut self) -> io::Result<()> {
+        write!(self.writer, "</span>")
+    }
+
+    fn text(&mut self, text: &str) -> io::Result<()> {
+        write!(self.writer, "{}", Escape(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use codespan::{CodeMap, Span};
+    use termcolor::NoColor;
+
+    use super::*;
+    use {emit, Label};
+
+    #[test]
+    fn escape() {
+        assert_eq!(
+            Escape("<a href=\"x\">'&'</a>").to_string(),
+            "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;",
+        );
+        assert_eq!(Escape("萤").to_string(), "萤");
+    }
+
+    #[test]
+    fn diagnostic_to_html() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "if a < b && c {}\n".to_string());
+        let span = |start: u32, len: i64| {
+            let start = file.byte_index(0.into(), start.into()).unwrap();
+            Span::from_offset(start, len.into())
+        };
+        let diagnostic = Diagnostic::new_error("expected `<bool>`")
+            .with_code("E0001")
+            .with_label(Label::new_primary(span(12, 1)).with_message("not a `<bool>`"))
+            .with_label(Label::new_secondary(span(3, 5)))
+            .with_note("a & b");
+
+        let mut output = Vec::new();
+        emit_html(&mut output, &Config::default(), &codemap, &diagnostic).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        let expected = "\
+<pre class=\"diagnostic\"><span class=\"header\"><span class=\"severity error\">error[E0001]</span>: \
+<span class=\"message\">expected `&lt;bool&gt;`</span></span>
+<span class=\"location\">- &lt;test&gt;:1:13</span>
+<span class=\"gutter\">1 | </span>if <span class=\"label secondary\">a &lt; b</span> &amp;&amp; \
+<span class=\"label primary error\">c</span> {}
+<span class=\"gutter\">  | </span>   <span class=\"label secondary\">-----</span>    \
+<span class=\"label primary error\">^</span> <span class=\"label primary error\">not a `&lt;bool&gt;`</span>
+<span class=\"footer\"><span class=\"gutter\">  = </span><span class=\"severity note\">note:</span> a &amp; b</span>
+</pre>
+";
+        assert_eq!(output, expected);
+
+        // The text of the HTML matches the output of `emit`
+        let mut text = NoColor::new(Vec::new());
+        emit(&mut text, &Config::default(), &codemap, &diagnostic).unwrap();
+        let text = String::from_utf8(text.into_inner()).unwrap();
+
+        let mut stripped = String::new();
+        let mut in_tag = false;
+        for ch in output.chars() {
+            match ch {
+                '<' => in_tag = true,
+                '>' => in_tag = false,
+                _ if !in_tag => stripped.push(ch),
+                _ => {},
+            }
+        }
+        let stripped = stripped
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&");
+        assert_eq!(stripped, text + "\n");
+    }
+}
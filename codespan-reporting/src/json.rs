@@ -0,0 +1,383 @@
+//! Machine readable output of diagnostics as JSON
+//!
+//! Each diagnostic is written as a single line containing a JSON object. The
+//! format closely follows that of `rustc --error-format=json`, so that editors
+//! and other tools that already consume `rustc`'s output can consume ours:
+//!
+//! ```json
+//! {
+//!   "message": "Unexpected type in `+` application",
+//!   "code": { "code": "E0001", "explanation": null },
+//!   "level": "error",
+//!   "spans": [
+//!     {
+//!       "file_name": "<test>",
+//!       "byte_start": 27,
+//!       "byte_end": 29,
+//!       "line_start": 3,
+//!       "line_end": 3,
+//!       "column_start": 9,
+//!       "column_end": 11,
+//!       "is_primary": true,
+//!       "text": [{ "text": "(+ test \"\")", "highlight_start": 9, "highlight_end": 11 }],
+//!       "label": "Expected integer but got string",
+//!       "suggested_replacement": null,
+//!       "suggestion_applicability": null
+//!     }
+//!   ],
+//!   "children": [],
+//!   "rendered": "error[E0001]: Unexpected type in `+` application\n..."
+//! }
+//! ```
+//!
+//! Byte offsets are relative to the start of the file, and line and column
+//! numbers start at 1. Columns are counted in characters. Labels with spans
+//! that are not in the codemap, or that do not lie within their file, are
+//! reported as child notes, as they are by `emit`. The explanation of a
+//! code is taken from `Config::registry`.
+
+use codespan::{CodeMap, LineIndex, RawIndex};
+use serde_json;
+use std::io;
+use termcolor::NoColor;
+
+use emitter::{emit, invalid_label_note, Config, Emitter, SpanLocation};
+use {Applicability, Diagnostic, LabelStyle, Severity};
+
+#[derive(Serialize)]
+struct JsonDiagnostic {
+    message: String,
+    code: Option<JsonCode>,
+    level: &'static str,
+    spans: Vec<JsonSpan>,
+    children: Vec<JsonDiagnostic>,
+    /// The diagnostic as it would be rendered by `emit`, without color
+    rendered: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JsonCode {
+    code: String,
+    explanation: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JsonSpan {
+    file_name: String,
+    byte_start: RawIndex,
+    byte_end: RawIndex,
+    line_start: RawIndex,
+    line_end: RawIndex,
+    column_start: RawIndex,
+    column_end: RawIndex,
+    is_primary: bool,
+    text: Vec<JsonSpanLine>,
+    label: Option<String>,
+    suggested_replacement: Option<String>,
+    suggestion_applicability: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct JsonSpanLine {
+    /// The source of the line, without the trailing newline
+    text: String,
+    highlight_start: RawIndex,
+    highlight_end: RawIndex,
+}
+
+impl JsonDiagnostic {
+    fn new(
+        config: &Config,
+        codemap: &CodeMap,
+        diagnostic: &Diagnostic,
+        rendered: Option<String>,
+    ) -> Self {
+        let mut spans = Vec::new();
+        let mut invalid = Vec::new();
+        for label in &diagnostic.labels {
+            match SpanLocation::new(codemap, label.span) {
+                Ok(location) => spans.push(JsonSpan::new(
+                    &location,
+                    label.style == LabelStyle::Primary,
+                    label.message.clone(),
+                )),
+                Err(reason) => invalid.push(JsonDiagnostic::note(
+                    Severity::Note,
+                    invalid_label_note(&config.catalog, label, &reason),
+                )),
+            }
+        }
+
+        let notes = diagnostic
+            .notes
+            .iter()
+            .map(|note| JsonDiagnostic::note(note.severity, note.message.clone()));
+        let suggestions = diagnostic.suggestions.iter().map(|suggestion| {
+            let location = SpanLocation::new(codemap, suggestion.span).ok();
+            let span = location.map(|location| JsonSpan {
+                suggested_replacement: Some(suggestion.replacement.clone()),
+                suggestion_applicability: Some(applicability_str(suggestion.applicability)),
+                ..JsonSpan::new(&location, true, None)
+            });
+
+            JsonDiagnostic {
+                message: suggestion.message.clone(),
+                code: None,
+                level: "help",
+                spans: span.into_iter().collect(),
+                children: Vec::new(),
+                rendered: None,
+            }
+        });
+        let children = diagnostic
+            .children
+            .iter()
+            .map(|child| JsonDiagnostic::new(config, codemap, child, None));
+
+        JsonDiagnostic {
+            message: diagnostic.message.clone(),
+            code: diagnostic.code.as_ref().map(|code| JsonCode {
+                code: code.clone(),
+                explanation: config
+                    .registry
+                    .as_ref()
+                    .and_then(|registry| registry.explanation(code))
+                    .map(str::to_owned),
+            }),
+            level: diagnostic.severity.to_str(),
+            spans,
+            children: invalid
+                .into_iter()
+                .chain(notes)
+                .chain(suggestions)
+                .chain(children)
+                .collect(),
+            rendered,
+        }
+    }
+
+    fn note(severity: Severity, message: String) -> Self {
+        JsonDiagnostic {
+            message,
+            code: None,
+            level: severity.to_str(),
+            spans: Vec::new(),
+            children: Vec::new(),
+            rendered: None,
+        }
+    }
+}
+
+impl JsonSpan {
+    fn new(location: &SpanLocation, is_primary: bool, label: Option<String>) -> Self {
+        let file = location.file;
+        let (line_start, column_start) = location.start;
+        let (line_end, column_end) = location.end;
+
+        let text = (line_start.0..line_end.0 + 1)
+            .map(LineIndex)
+            .map(|line| {
+                let line_span = file.line_span(line).expect("line_span");
+                let src = file.src_slice(line_span).expect("line_src");
+                let src = src.trim_end_matches(['\r', '\n']);
+
+                JsonSpanLine {
+                    text: src.to_owned(),
+                    highlight_start: if line == line_start {
+                        column_start.0 + 1
+                    } else {
+                        1
+                    },
+                    highlight_end: if line == line_end {
+                        column_end.0 + 1
+                    } else {
+                        src.chars().count() as RawIndex + 1
+                    },
+                }
+            })
+            .collect();
+
+        let byte_start = location.byte_offset().0 as RawIndex;
+        JsonSpan {
+            file_name: file.name().to_string(),
+            byte_start,
+            byte_end: byte_start + (location.span.end() - location.span.start()).0 as RawIndex,
+            line_start: line_start.0 + 1,
+            line_end: line_end.0 + 1,
+            column_start: column_start.0 + 1,
+            column_end: column_end.0 + 1,
+            is_primary,
+            text,
+            label,
+            suggested_replacement: None,
+            suggestion_applicability: None,
+        }
+    }
+}
+
+fn applicability_str(applicability: Applicability) -> &'static str {
+    match applicability {
+        Applicability::MachineApplicable => "MachineApplicable",
+        Applicability::MaybeIncorrect => "MaybeIncorrect",
+        Applicability::HasPlaceholders => "HasPlaceholders",
+    }
+}
+
+/// Write a diagnostic as a single line of JSON.
+///
+/// The `rendered` field contains the diagnostic as it would be displayed by
+/// `emit` with the given `config`.
+pub fn emit_json<W>(
+    mut writer: W,
+    config: &Config,
+    codemap: &CodeMap,
+    diagnostic: &Diagnostic,
+) -> io::Result<()>
+where
+    W: io::Write,
+{
+    let mut rendered = NoColor::new(Vec::new());
+    emit(&mut rendered, config, codemap, diagnostic)?;
+    let rendered = String::from_utf8_lossy(&rendered.into_inner()).into_owned();
+
+    // The level stays in English, as it is read by tools rather than people
+    let json = JsonDiagnostic::new(
+        config,
+        codemap,
+        &config.catalog.localize(diagnostic),
+        Some(rendered),
+    );
+    serde_json::to_writer(&mut writer, &json)?;
+    writeln!(writer)
+}
+
+/// Writes each diagnostic as a single line of JSON, using `emit_json`
+#[derive(Clone, Debug)]
+pub struct JsonEmitter<W> {
+    pub writer: W,
+    pub config: Config,
+}
+
+impl<W: io::Write> JsonEmitter<W> {
+    pub fn new(writer: W, config: Config) -> JsonEmitter<W> {
+        JsonEmitter { writer, config }
+    }
+}
+
+impl<W: io::Write> Emitter for JsonEmitter<W> {
+    fn emit(&mut self, codemap: &CodeMap, diagnostic: &Diagnostic) -> io::Result<()> {
+        emit_json(&mut self.writer, &self.config, codemap, diagnostic)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use codespan::{CodeMap, Span};
+    use serde_json::{json, Value};
+
+    use super::*;
+    use {Label, Registry, Suggestion};
+
+    #[test]
+    fn diagnostic_to_json() {
+        let mut codemap = CodeMap::new();
+        codemap.add_filemap("other".into(), "padding".into());
+        let file = codemap.add_filemap("test".into(), "(define x\n  \"萤\")\n".into());
+        let index = |line: u32, column: u32| file.byte_index(line.into(), column.into()).unwrap();
+
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_code("E0001")
+            .with_label(
+                Label::new_primary(Span::new(index(1, 2), index(1, 7))).with_message("here"),
+            )
+            .with_label(Label::new_secondary(Span::new(index(0, 1), index(1, 8))))
+            .with_note("a note")
+            .with_suggestion(Suggestion::new(
+                Span::new(index(1, 2), index(1, 7)),
+                "use a number",
+                "1",
+                Applicability::MaybeIncorrect,
+            ));
+
+        let mut output = Vec::new();
+        emit_json(&mut output, &Config::default(), &codemap, &diagnostic).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().count(), 1);
+
+        let json: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(json["message"], "oops");
+        assert_eq!(json["code"]["code"], "E0001");
+        assert_eq!(json["level"], "error");
+        assert!(json["rendered"]
+            .as_str()
+            .unwrap()
+            .starts_with("error[E0001]: oops\n"));
+
+        let primary = &json["spans"][0];
+        assert_eq!(primary["file_name"], "<test>");
+        assert_eq!(primary["byte_start"], 12);
+        assert_eq!(primary["byte_end"], 17);
+        assert_eq!(primary["line_start"], 2);
+        assert_eq!(primary["line_end"], 2);
+        assert_eq!(primary["column_start"], 3);
+        assert_eq!(primary["column_end"], 6);
+        assert_eq!(primary["is_primary"], true);
+        assert_eq!(primary["label"], "here");
+        assert_eq!(
+            primary["text"][0],
+            json!({ "text": "  \"萤\")", "highlight_start": 3, "highlight_end": 6 }),
+        );
+
+        let secondary = &json["spans"][1];
+        assert_eq!(secondary["is_primary"], false);
+        assert_eq!(secondary["label"], Value::Null);
+        assert_eq!(
+            secondary["text"],
+            json!([
+                { "text": "(define x", "highlight_start": 2, "highlight_end": 10 },
+                { "text": "  \"萤\")", "highlight_start": 1, "highlight_end": 7 },
+            ]),
+        );
+
+        let children = json["children"].as_array().unwrap();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0]["level"], "note");
+        assert_eq!(children[0]["message"], "a note");
+        assert_eq!(children[1]["level"], "help");
+        assert_eq!(children[1]["spans"][0]["suggested_replacement"], "1");
+        assert_eq!(
+            children[1]["spans"][0]["suggestion_applicability"],
+            "MaybeIncorrect",
+        );
+    }
+
+    #[test]
+    fn explanations_and_invalid_spans() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "(define x 1)\n".into());
+        let start = file.span().start();
+
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_code("E0001")
+            .with_label(Label::new_primary(Span::from_offset(start, 100.into())));
+        let config = Config {
+            registry: Some(Registry::new().with_explanation("E0001", "# Oops")),
+            ..Config::default()
+        };
+
+        let mut output = Vec::new();
+        emit_json(&mut output, &config, &codemap, &diagnostic).unwrap();
+        let json: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(json["code"]["explanation"], "# Oops");
+        assert_eq!(json["spans"], json!([]));
+        assert_eq!(json["children"][0]["level"], "note");
+        assert_eq!(
+            json["children"][0]["message"],
+            "could not show a label: the span 1..101 extends past the end of <test>",
+        );
+    }
+}
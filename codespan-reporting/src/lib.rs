@@ -1,16 +1,65 @@
+//! Diagnostic reporting support for the codespan crate
+//!
+//! # Optional Features
+//!
+//! Extra functionality is accessible by enabling feature flags. The features
+//! currently available are:
+//!
+//! - **serialization** - Adds `Serialize` and `Deserialize` implementations
+//!   for `Diagnostic` and the types that it contains, for use with `serde`
+//! - **cli** - Builds the `codespan-render` binary, which renders diagnostics
+//!   that have been serialized as JSON lines
+
 extern crate codespan;
+#[macro_use]
+extern crate log;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
 pub extern crate termcolor;
+extern crate terminal_size;
+extern crate unicode_width;
+extern crate url;
+
+#[cfg(test)]
+extern crate jsonschema;
 
 use std::cmp::Ordering;
 use std::fmt;
 use std::str::FromStr;
 use termcolor::{Color, ColorChoice};
 
+mod annotations;
 mod diagnostic;
 mod emitter;
+mod explain;
+mod fix;
+mod html;
+mod json;
+mod lint;
+mod locale;
+mod sarif;
+mod sink;
+mod snippet;
+mod styles;
+mod ui_test;
 
-pub use self::diagnostic::{Diagnostic, Label, LabelStyle};
-pub use self::emitter::emit;
+pub use self::annotations::{Annotation, AnnotationChecker};
+pub use self::diagnostic::{Applicability, Diagnostic, IntoDiagnostic, Label, LabelStyle, Note,
+                           Suggestion};
+pub use self::emitter::{emit, terminal_width, Config, DisplayStyle, Emitter, Glyphs, TeeEmitter,
+                        TermEmitter};
+pub use self::explain::{emit_explanation, Registry};
+pub use self::fix::{apply_suggestions, Conflict, FixedFile, Fixes};
+pub use self::html::{emit_html, HtmlEmitter, HTML_STYLESHEET};
+pub use self::json::{emit_json, JsonEmitter};
+pub use self::lint::{Level, LintLevels};
+pub use self::locale::{Catalog, ENGLISH};
+pub use self::sarif::{emit_sarif, SarifEmitter, SarifTool, SARIF_SCHEMA};
+pub use self::sink::DiagnosticSink;
+pub use self::styles::Styles;
+pub use self::ui_test::UiTests;
 
 /// A severity level for diagnostic messages
 ///
@@ -24,7 +73,8 @@ pub use self::emitter::emit;
 /// assert!(Severity::Warning > Severity::Note);
 /// assert!(Severity::Note > Severity::Help);
 /// ```
-#[derive(Copy, Clone, PartialEq, Hash, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serialization", derive(Deserialize, Serialize))]
 pub enum Severity {
     /// An unexpected bug.
     Bug,
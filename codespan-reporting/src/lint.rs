@@ -0,0 +1,167 @@
+//! Overriding the severity of diagnostics by their code
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use {Diagnostic, Severity};
+
+/// How diagnostics with a given code should be reported
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    /// Don't report the diagnostic at all
+    Allow,
+    /// Report the diagnostic as a warning
+    Warn,
+    /// Report the diagnostic as an error
+    Deny,
+    /// Report the diagnostic as an error, and don't allow the level to be
+    /// changed again
+    Forbid,
+}
+
+impl Level {
+    /// The names that a level can be parsed from
+    pub const VARIANTS: &'static [&'static str] = &["allow", "warn", "deny", "forbid"];
+}
+
+impl FromStr for Level {
+    type Err = &'static str;
+
+    fn from_str(src: &str) -> Result<Level, &'static str> {
+        match src {
+            _ if src.eq_ignore_ascii_case("allow") => Ok(Level::Allow),
+            _ if src.eq_ignore_ascii_case("warn") => Ok(Level::Warn),
+            _ if src.eq_ignore_ascii_case("deny") => Ok(Level::Deny),
+            _ if src.eq_ignore_ascii_case("forbid") => Ok(Level::Forbid),
+            _ => Err("valid values: allow, warn, deny, forbid"),
+        }
+    }
+}
+
+/// A table of the levels that diagnostics should be reported at, keyed by
+/// `Diagnostic::code`
+///
+/// This is usually built from command line options like `--allow E0012`,
+/// `--deny W0003` and `-Werror`:
+///
+/// ```rust
+/// use codespan_reporting::{Diagnostic, Level, LintLevels, Severity};
+///
+/// let levels = LintLevels::new()
+///     .with_level("E0012", Level::Allow)
+///     .with_warnings_as_errors(true);
+///
+/// let unused = Diagnostic::new_warning("unused variable").with_code("W0003");
+/// assert_eq!(levels.apply(unused).unwrap().severity, Severity::Error);
+///
+/// let allowed = Diagnostic::new_error("shadowed import").with_code("E0012");
+/// assert!(levels.apply(allowed).is_none());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LintLevels {
+    /// The level that each code is reported at. Codes that are not in the
+    /// table keep the severity that they were created with.
+    pub levels: HashMap<String, Level>,
+    /// Whether all warnings are reported as errors, after applying `levels`
+    pub warnings_as_errors: bool,
+}
+
+impl LintLevels {
+    pub fn new() -> LintLevels {
+        LintLevels::default()
+    }
+
+    pub fn with_level<S: Into<String>>(mut self, code: S, level: Level) -> LintLevels {
+        self.set(code, level);
+        self
+    }
+
+    pub fn with_warnings_as_errors(mut self, warnings_as_errors: bool) -> LintLevels {
+        self.warnings_as_errors = warnings_as_errors;
+        self
+    }
+
+    /// Set the level of a code. Codes that have been forbidden keep their
+    /// level, so that options given later can't allow them again.
+    pub fn set<S: Into<String>>(&mut self, code: S, level: Level) {
+        let current = self.levels.entry(code.into()).or_insert(level);
+        if *current != Level::Forbid {
+            *current = level;
+        }
+    }
+
+    /// The level of a code, if it has been set
+    pub fn level(&self, code: &str) -> Option<Level> {
+        self.levels.get(code).cloned()
+    }
+
+    /// The severity that a diagnostic should be reported at, or `None` if it
+    /// should be dropped. Bugs are always reported as they are.
+    pub fn severity(&self, diagnostic: &Diagnostic) -> Option<Severity> {
+        let level = diagnostic.code.as_ref().and_then(|code| self.level(code));
+        let severity = match (diagnostic.severity, level) {
+            (Severity::Bug, _) => return Some(Severity::Bug),
+            (_, Some(Level::Allow)) => return None,
+            (_, Some(Level::Warn)) => Severity::Warning,
+            (_, Some(Level::Deny)) | (_, Some(Level::Forbid)) => Severity::Error,
+            (severity, None) => severity,
+        };
+
+        match severity {
+            Severity::Warning if self.warnings_as_errors => Some(Severity::Error),
+            severity => Some(severity),
+        }
+    }
+
+    /// Update the severity of a diagnostic, returning `None` if it should be
+    /// dropped. The children of the diagnostic are left as they are.
+    pub fn apply(&self, mut diagnostic: Diagnostic) -> Option<Diagnostic> {
+        diagnostic.severity = self.severity(&diagnostic)?;
+        Some(diagnostic)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels() {
+        let levels = LintLevels::new()
+            .with_level("A", Level::Allow)
+            .with_level("W", Level::Warn)
+            .with_level("D", Level::Deny)
+            .with_level("F", Level::Forbid)
+            .with_level("F", Level::Allow);
+        let severity = |severity, code: &str| {
+            levels.severity(&Diagnostic::new(severity, "oops").with_code(code))
+        };
+
+        assert_eq!(levels.level("F"), Some(Level::Forbid));
+        assert_eq!(severity(Severity::Error, "A"), None);
+        assert_eq!(severity(Severity::Note, "A"), None);
+        assert_eq!(severity(Severity::Error, "W"), Some(Severity::Warning));
+        assert_eq!(severity(Severity::Warning, "D"), Some(Severity::Error));
+        assert_eq!(severity(Severity::Warning, "F"), Some(Severity::Error));
+        assert_eq!(severity(Severity::Warning, "X"), Some(Severity::Warning));
+        assert_eq!(severity(Severity::Bug, "A"), Some(Severity::Bug));
+        assert_eq!(
+            levels.severity(&Diagnostic::new_note("no code")),
+            Some(Severity::Note),
+        );
+    }
+
+    #[test]
+    fn warnings_as_errors() {
+        let levels = LintLevels::new()
+            .with_level("E", Level::Warn)
+            .with_warnings_as_errors(true);
+
+        let warning = Diagnostic::new_warning("oops");
+        assert_eq!(levels.severity(&warning), Some(Severity::Error));
+        let warning = Diagnostic::new_error("oops").with_code("E");
+        assert_eq!(levels.severity(&warning), Some(Severity::Error));
+        let note = Diagnostic::new_note("oops");
+        assert_eq!(levels.severity(&note), Some(Severity::Note));
+    }
+}
@@ -0,0 +1,408 @@
+//! Translation of diagnostics, and the text around them, into other languages
+//!
+//! Messages are looked up in a `Catalog` when diagnostics are emitted. A
+//! diagnostic, or one of its labels, notes or suggestions, can be given the
+//! id of a message in the catalog with `with_message_id`, and its message is
+//! then replaced by the translation if there is one. Messages without an id,
+//! or whose id is not in the catalog, are shown as they are, so diagnostics
+//! don't need to be translated all at once.
+//!
+//! ```rust
+//! use codespan_reporting::{Catalog, Diagnostic};
+//!
+//! let catalog = Catalog::new("fr")
+//!     .with_message("unknown-variable", "variable `{ $name }` inconnue");
+//! let diagnostic = Diagnostic::new_error("unknown variable `x`")
+//!     .with_message_id("unknown-variable")
+//!     .with_arg("name", "x");
+//!
+//! assert_eq!(catalog.localize(&diagnostic).message, "variable `x` inconnue");
+//! ```
+//!
+//! Catalogs are written in a subset of the [Fluent] syntax:
+//!
+//! ```text
+//! # Comments start with a `#`
+//! unknown-variable = variable `{ $name }` inconnue
+//! did-you-mean =
+//!     vouliez-vous dire `{ $suggestion }` ?
+//!     (sur plusieurs lignes)
+//! ```
+//!
+//! Placeables like `{ $name }` are filled with the arguments of the
+//! diagnostic, and `{ "{" }` can be used for a literal brace. Selectors and
+//! message references are not supported. Where a message depends on a
+//! number, as in the summaries written by `DiagnosticSink`, it has `-one`
+//! and `-other` variants.
+//!
+//! The severity names and other text written by the emitters are also
+//! looked up in the catalog, and fall back to the English messages in
+//! `ENGLISH`.
+//!
+//! [Fluent]: https://projectfluent.org/fluent/guide/
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use {Diagnostic, Severity};
+
+/// The English messages used for the severity names and other text written
+/// by the emitters, which other catalogs fall back to
+pub const ENGLISH: &str = "\
+severity-bug = error: internal compiler error
+severity-error = error
+severity-warning = warning
+severity-note = note
+severity-help = help
+
+invalid-label = could not show a label: { $reason }
+invalid-label-with-message = could not show the label `{ $label }`: { $reason }
+
+aborting-one = aborting due to previous error
+aborting-other = aborting due to { $count } previous errors
+warnings-emitted-one = { $count } warning emitted
+warnings-emitted-other = { $count } warnings emitted
+aborting-with-warnings = { $aborting }; { $warnings }
+stopped-after-one = stopped after 1 error
+stopped-after-other = stopped after { $count } errors
+error-limit-one = { $stopped }, so 1 more diagnostic was not shown
+error-limit-other = { $stopped }, so { $count } more diagnostics were not shown
+
+explain-hint-one = For more information about this error, try `{ $command } { $code }`.
+explain-hint-other =
+    Some errors have detailed explanations: { $codes }.
+    For more information about an error, try `{ $command } { $code }`.
+";
+
+/// The messages of a single locale, used to translate diagnostics when they
+/// are emitted
+#[derive(Clone, Debug, PartialEq)]
+pub struct Catalog {
+    /// The language tag of the locale, like `fr` or `pt-BR`
+    pub locale: String,
+    messages: HashMap<String, String>,
+    /// The catalog used for messages that are missing from this one
+    fallback: Option<Box<Catalog>>,
+}
+
+impl Default for Catalog {
+    fn default() -> Catalog {
+        Catalog::english()
+    }
+}
+
+impl Catalog {
+    /// An empty catalog for a locale, which falls back to English
+    pub fn new<S: Into<String>>(locale: S) -> Catalog {
+        Catalog {
+            locale: locale.into(),
+            messages: HashMap::new(),
+            fallback: Some(Box::new(Catalog::english())),
+        }
+    }
+
+    /// The English messages used by the emitters
+    pub fn english() -> Catalog {
+        let mut catalog = Catalog {
+            locale: "en".to_owned(),
+            messages: HashMap::new(),
+            fallback: None,
+        };
+        catalog.add_messages(ENGLISH).expect("ENGLISH");
+        catalog
+    }
+
+    /// Parse a catalog in the Fluent syntax, which falls back to English
+    pub fn parse<S: Into<String>>(locale: S, src: &str) -> Result<Catalog, String> {
+        let mut catalog = Catalog::new(locale);
+        catalog.add_messages(src)?;
+        Ok(catalog)
+    }
+
+    pub fn with_message<S, T>(mut self, id: S, message: T) -> Catalog
+    where
+        S: Into<String>,
+        T: Into<String>,
+    {
+        self.add(id, message);
+        self
+    }
+
+    /// Use another catalog for the messages that are missing from this one,
+    /// rather than English
+    pub fn with_fallback(mut self, fallback: Catalog) -> Catalog {
+        self.fallback = Some(Box::new(fallback));
+        self
+    }
+
+    /// Add a message, replacing any previous message with the same id
+    pub fn add<S, T>(&mut self, id: S, message: T)
+    where
+        S: Into<String>,
+        T: Into<String>,
+    {
+        self.messages.insert(id.into(), message.into());
+    }
+
+    /// Add the messages in a string in the Fluent syntax
+    pub fn add_messages(&mut self, src: &str) -> Result<(), String> {
+        let mut current: Option<(String, String)> = None;
+
+        for (i, line) in src.lines().enumerate() {
+            let is_continuation = line.starts_with(|ch: char| ch.is_whitespace());
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            } else if is_continuation {
+                match current {
+                    Some((_, ref mut message)) => {
+                        if !message.is_empty() {
+                            message.push('\n');
+                        }
+                        message.push_str(line.trim());
+                    },
+                    None => return Err(format!("line {}: unexpected indentation", i + 1)),
+                }
+                continue;
+            }
+
+            let (id, message) = match line.find('=') {
+                Some(index) => (line[..index].trim(), line[index + 1..].trim()),
+                None => return Err(format!("line {}: expected `id = message`", i + 1)),
+            };
+            let is_valid_id = id.starts_with(|ch: char| ch.is_ascii_alphabetic())
+                && id
+                    .chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_');
+            if !is_valid_id {
+                return Err(format!("line {}: invalid message id `{}`", i + 1, id));
+            }
+
+            if let Some((id, message)) = current.take() {
+                self.add(id, message);
+            }
+            current = Some((id.to_owned(), message.to_owned()));
+        }
+
+        if let Some((id, message)) = current {
+            self.add(id, message);
+        }
+        Ok(())
+    }
+
+    /// The message with the given id, from this catalog or its fallback
+    pub fn message(&self, id: &str) -> Option<&str> {
+        match self.messages.get(id) {
+            Some(message) => Some(message),
+            None => self.fallback.as_ref()?.message(id),
+        }
+    }
+
+    /// Format the message with the given id, filling in its placeables with
+    /// `args`. Returns `None` if the message is not in the catalog.
+    pub fn format(&self, id: &str, args: &[(String, String)]) -> Option<String> {
+        self.message(id).map(|message| fill(message, args))
+    }
+
+    /// Format the `-one` or `-other` variant of a message, depending on
+    /// `count`, which is available to the message as `{ $count }`
+    pub fn format_count(&self, id: &str, count: usize, args: &[(String, String)]) -> String {
+        let variant = match count {
+            1 => format!("{}-one", id),
+            _ => format!("{}-other", id),
+        };
+        let mut args = args.to_vec();
+        args.push(("count".to_owned(), count.to_string()));
+        self.format(&variant, &args).unwrap_or(variant)
+    }
+
+    /// The name of a severity, as shown in the headers of diagnostics
+    pub fn severity(&self, severity: Severity) -> &str {
+        let id = match severity {
+            Severity::Bug => "severity-bug",
+            Severity::Error => "severity-error",
+            Severity::Warning => "severity-warning",
+            Severity::Note => "severity-note",
+            Severity::Help => "severity-help",
+        };
+        self.message(id).unwrap_or_else(|| severity.to_str())
+    }
+
+    /// Translate the messages of a diagnostic and its children that have
+    /// message ids. The arguments of a diagnostic are available to the
+    /// messages of its labels, notes and suggestions, and to its children.
+    ///
+    /// The diagnostic is only copied if it has message ids.
+    pub fn localize<'a>(&self, diagnostic: &'a Diagnostic) -> Cow<'a, Diagnostic> {
+        if has_message_ids(diagnostic) {
+            Cow::Owned(self.localize_with(diagnostic, &[]))
+        } else {
+            Cow::Borrowed(diagnostic)
+        }
+    }
+
+    fn localize_with(
+        &self,
+        diagnostic: &Diagnostic,
+        parent_args: &[(String, String)],
+    ) -> Diagnostic {
+        let mut args = diagnostic.args.clone();
+        args.extend(parent_args.iter().cloned());
+        let translate = |id: &Option<String>| id.as_ref().and_then(|id| self.format(id, &args));
+
+        let mut diagnostic = diagnostic.clone();
+        if let Some(translated) = translate(&diagnostic.message_id) {
+            diagnostic.message = translated;
+        }
+        for label in &mut diagnostic.labels {
+            if let Some(translated) = translate(&label.message_id) {
+                label.message = Some(translated);
+            }
+        }
+        for note in &mut diagnostic.notes {
+            if let Some(translated) = translate(&note.message_id) {
+                note.message = translated;
+            }
+        }
+        for suggestion in &mut diagnostic.suggestions {
+            if let Some(translated) = translate(&suggestion.message_id) {
+                suggestion.message = translated;
+            }
+        }
+        for child in &mut diagnostic.children {
+            *child = self.localize_with(child, &args);
+        }
+        diagnostic
+    }
+}
+
+/// Whether any of the messages of a diagnostic or its children have ids
+fn has_message_ids(diagnostic: &Diagnostic) -> bool {
+    diagnostic.message_id.is_some()
+        || diagnostic
+            .labels
+            .iter()
+            .any(|label| label.message_id.is_some())
+        || diagnostic.notes.iter().any(|note| note.message_id.is_some())
+        || diagnostic
+            .suggestions
+            .iter()
+            .any(|suggestion| suggestion.message_id.is_some())
+        || diagnostic.children.iter().any(has_message_ids)
+}
+
+/// Fill in the placeables of a message
+fn fill(message: &str, args: &[(String, String)]) -> String {
+    let mut filled = String::with_capacity(message.len());
+    let mut rest = message;
+
+    while let Some(start) = rest.find('{') {
+        // String literals may contain braces, so the end of the placeable is
+        // searched for after the closing quote
+        let inner = &rest[start + 1..];
+        let literal_len = match inner.trim_start().strip_prefix('"') {
+            Some(literal) => literal.find('"').map_or(0, |len| len + 2),
+            None => 0,
+        };
+        let skipped = inner.len() - inner.trim_start().len() + literal_len;
+        let end = match inner[skipped..].find('}') {
+            Some(end) => start + 1 + skipped + end,
+            None => break,
+        };
+
+        filled.push_str(&rest[..start]);
+
+        let placeable = rest[start + 1..end].trim();
+        let arg = placeable.strip_prefix('$').and_then(|name| {
+            args.iter()
+                .find(|(arg, _)| arg == name)
+                .map(|(_, value)| value.as_str())
+        });
+        let literal = placeable
+            .strip_prefix('"')
+            .and_then(|placeable| placeable.strip_suffix('"'));
+        match arg.or(literal) {
+            Some(value) => filled.push_str(value),
+            // Leave unknown placeables in the message, so that they can be
+            // spotted
+            None => filled.push_str(&rest[start..end + 1]),
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    filled.push_str(rest);
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Label, Note};
+
+    const FRENCH: &str = "\
+# Messages d'erreur
+severity-error = erreur
+severity-warning = avertissement
+
+unknown-variable = variable `{ $name }` inconnue
+not-found = introuvable
+did-you-mean =
+    vouliez-vous dire `{ $suggestion }` ?
+    { \"{\" }sic{ \"}\" } { $missing }
+";
+
+    #[test]
+    fn parse_and_format() {
+        let catalog = Catalog::parse("fr", FRENCH).unwrap();
+        let args = vec![("suggestion".to_owned(), "foo".to_owned())];
+
+        assert_eq!(
+            catalog.format("did-you-mean", &args).unwrap(),
+            "vouliez-vous dire `foo` ?\n{sic} { $missing }",
+        );
+        assert_eq!(catalog.severity(Severity::Error), "erreur");
+        // Missing messages fall back to English
+        assert_eq!(catalog.severity(Severity::Help), "help");
+        assert_eq!(
+            catalog.format_count("aborting", 2, &[]),
+            "aborting due to 2 previous errors"
+        );
+        assert_eq!(catalog.format("missing", &args), None);
+
+        assert_eq!(
+            Catalog::parse("fr", "  indented").unwrap_err(),
+            "line 1: unexpected indentation",
+        );
+        assert_eq!(
+            Catalog::parse("fr", "a = b\n$c = d").unwrap_err(),
+            "line 2: invalid message id `$c`",
+        );
+    }
+
+    #[test]
+    fn localize() {
+        let catalog = Catalog::parse("fr", FRENCH).unwrap();
+        let diagnostic = Diagnostic::new_error("unknown variable `x`")
+            .with_message_id("unknown-variable")
+            .with_arg("name", "x")
+            .with_label(Label::new_primary(Default::default()).with_message_id("not-found"))
+            .with_note("untranslated")
+            .with_notes(vec![Note::new_note("missing").with_message_id("missing")])
+            .with_child(Diagnostic::new_note("unknown").with_message_id("unknown-variable"));
+
+        let localized = catalog.localize(&diagnostic);
+        assert_eq!(localized.message, "variable `x` inconnue");
+        assert_eq!(localized.labels[0].message, Some("introuvable".to_owned()));
+        assert_eq!(localized.notes[0].message, "untranslated");
+        assert_eq!(localized.notes[1].message, "missing");
+        assert_eq!(localized.children[0].message, "variable `x` inconnue");
+
+        // Messages without ids are never looked up, even if they happen to
+        // match an id in the catalog
+        let diagnostic = Diagnostic::new_error("severity-error").with_note("unknown-variable");
+        match catalog.localize(&diagnostic) {
+            Cow::Borrowed(localized) => assert_eq!(*localized, diagnostic),
+            Cow::Owned(_) => panic!("diagnostic without message ids was copied"),
+        }
+    }
+}
@@ -0,0 +1,655 @@
+//! Export of diagnostics in the [Static Analysis Results Interchange Format
+//! (SARIF)](https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html),
+//! version 2.1.0
+//!
+//! A batch of diagnostics is written as a SARIF log containing a single run.
+//! Each diagnostic becomes a result, with its primary labels as the result's
+//! locations and its secondary labels, and the labels of its children, as
+//! related locations. Machine readable suggestions are recorded as fixes.
+//! Messages are translated with `Config::catalog`, as they are by `emit`.
+//!
+//! Absolute paths are written as `file` URIs. Relative paths are written as
+//! relative references against the `%SRCROOT%` base, which consumers of the
+//! log resolve to the root of the source tree.
+
+use codespan::{CodeMap, FileName};
+use serde_json;
+use std::io;
+use std::path::{Component, Path};
+use url::percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
+use url::Url;
+
+use emitter::{invalid_label_note, Config, Emitter, SpanLocation};
+use {Diagnostic, LabelStyle, Severity};
+
+/// The URI of the SARIF 2.1.0 JSON schema
+pub const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// A description of the tool that produced the diagnostics, recorded in the
+/// SARIF log
+#[derive(Clone, Debug)]
+pub struct SarifTool {
+    /// The name of the tool, for example `"my-compiler"`
+    pub name: String,
+    /// The version of the tool
+    pub version: Option<String>,
+    /// A URI pointing to more information about the tool
+    pub information_uri: Option<String>,
+}
+
+impl SarifTool {
+    pub fn new<S: Into<String>>(name: S) -> SarifTool {
+        SarifTool {
+            name: name.into(),
+            version: None,
+            information_uri: None,
+        }
+    }
+
+    pub fn with_version<S: Into<String>>(mut self, version: S) -> SarifTool {
+        self.version = Some(version.into());
+        self
+    }
+
+    pub fn with_information_uri<S: Into<String>>(mut self, information_uri: S) -> SarifTool {
+        self.information_uri = Some(information_uri.into());
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct Log {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<Run>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Run {
+    tool: Tool,
+    results: Vec<SarifResult>,
+    column_kind: &'static str,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    driver: ToolComponent,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolComponent {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    information_uri: Option<String>,
+    rules: Vec<ReportingDescriptor>,
+}
+
+#[derive(Serialize)]
+struct ReportingDescriptor {
+    id: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rule_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rule_index: Option<usize>,
+    level: &'static str,
+    message: Message,
+    locations: Vec<Location>,
+    related_locations: Vec<Location>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fixes: Vec<Fix>,
+}
+
+#[derive(Serialize)]
+struct Message {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Location {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<usize>,
+    physical_location: PhysicalLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<Message>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+    region: Region,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ArtifactLocation {
+    uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri_base_id: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+    byte_offset: usize,
+    byte_length: usize,
+    snippet: ArtifactContent,
+}
+
+#[derive(Serialize)]
+struct ArtifactContent {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Fix {
+    description: Message,
+    artifact_changes: Vec<ArtifactChange>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ArtifactChange {
+    artifact_location: ArtifactLocation,
+    replacements: Vec<Replacement>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Replacement {
+    deleted_region: Region,
+    inserted_content: ArtifactContent,
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Bug | Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note | Severity::Help => "note",
+    }
+}
+
+fn artifact_location(name: &FileName) -> ArtifactLocation {
+    match *name {
+        FileName::Real(ref path) => match Url::from_file_path(path) {
+            Ok(url) => ArtifactLocation {
+                uri: url.into_string(),
+                uri_base_id: None,
+            },
+            Err(()) => ArtifactLocation {
+                uri: relative_uri(path),
+                uri_base_id: Some("%SRCROOT%"),
+            },
+        },
+        FileName::Virtual(ref name) => ArtifactLocation {
+            uri: relative_uri(Path::new(name.as_ref())),
+            uri_base_id: None,
+        },
+    }
+}
+
+/// A relative reference to a path, with each of its components
+/// percent-encoded
+fn relative_uri(path: &Path) -> String {
+    let segments = path.components().filter_map(|component| match component {
+        Component::Normal(segment) => Some(
+            utf8_percent_encode(&segment.to_string_lossy(), PATH_SEGMENT_ENCODE_SET).to_string(),
+        ),
+        Component::ParentDir => Some("..".to_owned()),
+        Component::CurDir | Component::RootDir | Component::Prefix(_) => None,
+    });
+    segments.collect::<Vec<_>>().join("/")
+}
+
+fn physical_location(location: &SpanLocation) -> PhysicalLocation {
+    let (start_line, start_column) = location.start;
+    let (end_line, end_column) = location.end;
+    let span = location.span;
+    let snippet = location.file.src_slice(span).expect("snippet");
+
+    PhysicalLocation {
+        artifact_location: artifact_location(location.file.name()),
+        region: Region {
+            start_line: start_line.to_usize() + 1,
+            start_column: start_column.to_usize() + 1,
+            end_line: end_line.to_usize() + 1,
+            end_column: end_column.to_usize() + 1,
+            byte_offset: location.byte_offset().to_usize(),
+            byte_length: (span.end() - span.start()).to_usize(),
+            snippet: ArtifactContent {
+                text: snippet.to_owned(),
+            },
+        },
+    }
+}
+
+fn result(
+    config: &Config,
+    codemap: &CodeMap,
+    rules: &mut Vec<String>,
+    diagnostic: &Diagnostic,
+) -> SarifResult {
+    let diagnostic = &config.catalog.localize(diagnostic);
+    let rule_index = diagnostic.code.as_ref().map(|code| {
+        rules
+            .iter()
+            .position(|rule| rule == code)
+            .unwrap_or_else(|| {
+                rules.push(code.clone());
+                rules.len() - 1
+            })
+    });
+
+    let mut locations = Vec::new();
+    let mut related_locations = Vec::new();
+    let mut invalid = Vec::new();
+
+    for label in &diagnostic.labels {
+        let physical_location = match SpanLocation::new(codemap, label.span) {
+            Ok(location) => physical_location(&location),
+            Err(reason) => {
+                invalid.push(invalid_label_note(&config.catalog, label, &reason));
+                continue;
+            },
+        };
+        let message = label.message.as_ref().map(|message| Message {
+            text: message.clone(),
+        });
+
+        match label.style {
+            LabelStyle::Primary => locations.push(Location {
+                id: None,
+                physical_location,
+                message,
+            }),
+            LabelStyle::Secondary => related_locations.push(Location {
+                id: Some(related_locations.len()),
+                physical_location,
+                message,
+            }),
+        }
+    }
+
+    for child in &diagnostic.children {
+        for label in &child.labels {
+            if let Ok(location) = SpanLocation::new(codemap, label.span) {
+                let text = match label.message {
+                    Some(ref message) => format!("{}: {}", child.message, message),
+                    None => child.message.clone(),
+                };
+                related_locations.push(Location {
+                    id: Some(related_locations.len()),
+                    physical_location: physical_location(&location),
+                    message: Some(Message { text }),
+                });
+            }
+        }
+    }
+
+    let fixes = diagnostic
+        .suggestions
+        .iter()
+        .filter_map(|suggestion| {
+            let location = SpanLocation::new(codemap, suggestion.span).ok()?;
+            let PhysicalLocation {
+                artifact_location,
+                region,
+            } = physical_location(&location);
+
+            Some(Fix {
+                description: Message {
+                    text: suggestion.message.clone(),
+                },
+                artifact_changes: vec![ArtifactChange {
+                    artifact_location,
+                    replacements: vec![Replacement {
+                        deleted_region: region,
+                        inserted_content: ArtifactContent {
+                            text: suggestion.replacement.clone(),
+                        },
+                    }],
+                }],
+            })
+        })
+        .collect();
+
+    // SARIF messages have no notion of footers, so notes are appended to the
+    // end of the message text, after those describing invalid labels
+    let mut text = diagnostic.message.clone();
+    let severity = |severity| config.catalog.severity(severity);
+    for message in invalid {
+        text.push_str(&format!("\n{}: {}", severity(Severity::Note), message));
+    }
+    for note in &diagnostic.notes {
+        text.push_str(&format!("\n{}: {}", severity(note.severity), note.message));
+    }
+
+    SarifResult {
+        rule_id: diagnostic.code.clone(),
+        rule_index,
+        level: level(diagnostic.severity),
+        message: Message { text },
+        locations,
+        related_locations,
+        fixes,
+    }
+}
+
+/// Collects diagnostics, and writes them as a SARIF log containing a single
+/// run of `tool` when the batch is finished.
+///
+/// The `code` of each diagnostic is used as the result's `ruleId`, and is
+/// recorded in the rules of the tool.
+pub struct SarifEmitter<W> {
+    pub writer: W,
+    pub tool: SarifTool,
+    pub config: Config,
+    rules: Vec<String>,
+    results: Vec<SarifResult>,
+}
+
+impl<W: io::Write> SarifEmitter<W> {
+    pub fn new(writer: W, tool: SarifTool, config: Config) -> SarifEmitter<W> {
+        SarifEmitter {
+            writer,
+            tool,
+            config,
+            rules: Vec::new(),
+            results: Vec::new(),
+        }
+    }
+}
+
+impl<W: io::Write> Emitter for SarifEmitter<W> {
+    fn emit(&mut self, codemap: &CodeMap, diagnostic: &Diagnostic) -> io::Result<()> {
+        let result = result(&self.config, codemap, &mut self.rules, diagnostic);
+        self.results.push(result);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let log = Log {
+            schema: SARIF_SCHEMA,
+            version: "2.1.0",
+            runs: vec![Run {
+                tool: Tool {
+                    driver: ToolComponent {
+                        name: self.tool.name.clone(),
+                        version: self.tool.version.clone(),
+                        information_uri: self.tool.information_uri.clone(),
+                        rules: self
+                            .rules
+                            .drain(..)
+                            .map(|id| ReportingDescriptor { id })
+                            .collect(),
+                    },
+                },
+                results: self.results.drain(..).collect(),
+                column_kind: "unicodeCodePoints",
+            }],
+        };
+
+        serde_json::to_writer_pretty(&mut self.writer, &log)?;
+        writeln!(self.writer)?;
+        self.writer.flush()
+    }
+}
+
+/// Write a batch of diagnostics as a SARIF log, containing a single run of
+/// `tool`.
+///
+/// The `code` of each diagnostic is used as the result's `ruleId`, and is
+/// recorded in the rules of the tool.
+pub fn emit_sarif<'a, W, Diagnostics>(
+    writer: W,
+    config: &Config,
+    tool: &SarifTool,
+    codemap: &CodeMap,
+    diagnostics: Diagnostics,
+) -> io::Result<()>
+where
+    W: io::Write,
+    Diagnostics: IntoIterator<Item = &'a Diagnostic>,
+{
+    let mut emitter = SarifEmitter::new(writer, tool.clone(), config.clone());
+    for diagnostic in diagnostics {
+        emitter.emit(codemap, diagnostic)?;
+    }
+    emitter.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use codespan::{CodeMap, FileName, Span};
+    use jsonschema;
+    use serde_json::json;
+    use serde_json::{self, Value};
+
+    use super::*;
+    use locale::Catalog;
+    use {Applicability, Label, Suggestion};
+
+    #[test]
+    fn sarif_log_matches_schema() {
+        let mut schema: Value =
+            serde_json::from_str(include_str!("../schemas/sarif-schema-2.1.0.json")).unwrap();
+        // The published schema contains an invalid regular expression for
+        // the `language` properties, which we never emit
+        for definition in &["run", "toolComponent"] {
+            schema["definitions"][definition]["properties"]["language"]
+                .as_object_mut()
+                .unwrap()
+                .remove("pattern");
+        }
+        let validator = jsonschema::validator_for(&schema).unwrap();
+
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "(define x 1)\n(define x \"萤\")\n".into());
+        let index = |line: u32, column: u32| file.byte_index(line.into(), column.into()).unwrap();
+
+        let diagnostics = vec![
+            Diagnostic::new_error("`x` is defined more than once")
+                .with_code("E0001")
+                .with_label(
+                    Label::new_primary(Span::new(index(1, 8), index(1, 9)))
+                        .with_message("redefined here"),
+                )
+                .with_note("definitions must be unique")
+                .with_child(
+                    Diagnostic::new_note("previous definition here")
+                        .with_label(Label::new_secondary(Span::new(index(0, 8), index(0, 9)))),
+                ),
+            Diagnostic::new_warning("string literal")
+                .with_code("W0002")
+                .with_label(Label::new_primary(Span::new(index(1, 10), index(1, 15))))
+                .with_suggestion(Suggestion::new(
+                    Span::new(index(1, 10), index(1, 15)),
+                    "use an integer",
+                    "2",
+                    Applicability::MaybeIncorrect,
+                )),
+            Diagnostic::new_help("no labels").with_code("E0001"),
+        ];
+
+        let mut output = Vec::new();
+        emit_sarif(
+            &mut output,
+            &Config::default(),
+            &SarifTool::new("test").with_version("1.0.0"),
+            &codemap,
+            &diagnostics,
+        )
+        .unwrap();
+        let log: Value = serde_json::from_slice(&output).unwrap();
+
+        let errors = validator
+            .iter_errors(&log)
+            .map(|error| error.to_string())
+            .collect::<Vec<_>>();
+        assert!(errors.is_empty(), "{:#?}", errors);
+
+        let run = &log["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "test");
+        assert_eq!(
+            run["tool"]["driver"]["rules"],
+            json!([{ "id": "E0001" }, { "id": "W0002" }]),
+        );
+
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0]["ruleId"], "E0001");
+        assert_eq!(results[0]["level"], "error");
+        assert_eq!(
+            results[0]["message"]["text"],
+            "`x` is defined more than once\nnote: definitions must be unique",
+        );
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"],
+            json!({
+                "artifactLocation": { "uri": "test" },
+                "region": {
+                    "startLine": 2,
+                    "startColumn": 9,
+                    "endLine": 2,
+                    "endColumn": 10,
+                    "byteOffset": 21,
+                    "byteLength": 1,
+                    "snippet": { "text": "x" },
+                },
+            }),
+        );
+        assert_eq!(
+            results[0]["relatedLocations"][0]["message"]["text"],
+            "previous definition here",
+        );
+
+        let region = &results[1]["locations"][0]["physicalLocation"]["region"];
+        assert_eq!(region["startColumn"], 11);
+        assert_eq!(region["endColumn"], 14);
+        assert_eq!(region["byteLength"], 5);
+        assert_eq!(
+            results[1]["fixes"][0]["artifactChanges"][0]["replacements"][0]["insertedContent"],
+            json!({ "text": "2" }),
+        );
+
+        assert_eq!(results[2]["ruleIndex"], 0);
+        assert_eq!(results[2]["level"], "note");
+    }
+
+    #[test]
+    fn emitter_writes_log_when_finished() {
+        let codemap = CodeMap::new();
+        let mut emitter = SarifEmitter::new(Vec::new(), SarifTool::new("test"), Config::default());
+
+        emitter
+            .emit(&codemap, &Diagnostic::new_error("first").with_code("E0001"))
+            .unwrap();
+        emitter
+            .emit(&codemap, &Diagnostic::new_warning("second"))
+            .unwrap();
+        assert!(emitter.writer.is_empty());
+
+        emitter.finish().unwrap();
+        let log: Value = serde_json::from_slice(&emitter.writer).unwrap();
+        let results = log["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["message"]["text"], "first");
+        assert_eq!(results[1]["level"], "warning");
+
+        // A new batch starts once the previous one is finished
+        emitter.writer.clear();
+        emitter
+            .emit(&codemap, &Diagnostic::new_note("third"))
+            .unwrap();
+        emitter.finish().unwrap();
+        let log: Value = serde_json::from_slice(&emitter.writer).unwrap();
+        assert_eq!(log["runs"][0]["results"].as_array().unwrap().len(), 1);
+        assert_eq!(log["runs"][0]["tool"]["driver"]["rules"], json!([]));
+    }
+
+    #[test]
+    fn uris() {
+        let mut codemap = CodeMap::new();
+        let relative = codemap.add_filemap(FileName::real("src/a b.txt"), "x".into());
+        let absolute = codemap.add_filemap(FileName::real("/tmp/probe/a b.txt"), "y".into());
+        let invalid = Span::from_offset(relative.span().start(), 5.into());
+        let diagnostics = vec![
+            Diagnostic::new_error("relative").with_label(Label::new_primary(relative.span())),
+            Diagnostic::new_error("absolute").with_label(Label::new_primary(absolute.span())),
+            Diagnostic::new_error("invalid").with_label(Label::new_primary(invalid)),
+        ];
+
+        let mut output = Vec::new();
+        let tool = SarifTool::new("test");
+        emit_sarif(&mut output, &Config::default(), &tool, &codemap, &diagnostics).unwrap();
+        let log: Value = serde_json::from_slice(&output).unwrap();
+        let results = &log["runs"][0]["results"];
+        let artifact_location =
+            |i: usize| &results[i]["locations"][0]["physicalLocation"]["artifactLocation"];
+
+        assert_eq!(
+            *artifact_location(0),
+            json!({ "uri": "src/a%20b.txt", "uriBaseId": "%SRCROOT%" }),
+        );
+        if cfg!(unix) {
+            assert_eq!(
+                *artifact_location(1),
+                json!({ "uri": "file:///tmp/probe/a%20b.txt" }),
+            );
+        }
+        assert_eq!(results[2]["locations"], json!([]));
+        assert_eq!(
+            results[2]["message"]["text"],
+            "invalid\nnote: could not show a label: \
+             the span 1..6 extends past the end of src/a b.txt",
+        );
+    }
+
+    #[test]
+    fn localized() {
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap("test".into(), "x".into());
+        let invalid = Span::from_offset(file.span().start(), 5.into());
+        let diagnostic = Diagnostic::new_error("oops")
+            .with_label(Label::new_primary(invalid))
+            .with_help("try again");
+        let catalog = Catalog::parse(
+            "de",
+            "severity-note = Hinweis\n\
+             severity-help = Hilfe\n\
+             invalid-label = Markierung nicht anzeigbar: { $reason }\n",
+        )
+        .unwrap();
+        let config = Config {
+            catalog,
+            ..Config::default()
+        };
+
+        let mut output = Vec::new();
+        let tool = SarifTool::new("test");
+        emit_sarif(&mut output, &config, &tool, &codemap, &[diagnostic]).unwrap();
+        let log: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(
+            log["runs"][0]["results"][0]["message"]["text"],
+            "oops\n\
+             Hinweis: Markierung nicht anzeigbar: the span 1..6 extends past the end of <test>\n\
+             Hilfe: try again",
+        );
+    }
+}
@@ -0,0 +1,388 @@
+//! Collecting the diagnostics reported while running a tool
+
+use codespan::{ByteIndex, CodeMap};
+use std::collections::HashSet;
+use std::io;
+
+use emitter::Emitter;
+use explain::Registry;
+use lint::LintLevels;
+use locale::Catalog;
+use {Diagnostic, LabelStyle, Severity};
+
+/// Collects the diagnostics reported by a tool, and passes them on to an
+/// emitter.
+///
+/// The severity of each diagnostic is first adjusted according to the lint
+/// levels, which may drop it entirely. The diagnostics are then counted by
+/// severity, so that the tool can decide how
+/// to exit, and diagnostics that are identical to one that has already been
+/// reported are ignored. Once everything has been reported, `finish` emits a
+/// summary such as `error: aborting due to 2 previous errors; 1 warning
+/// emitted`, along with a hint to run `--explain` for any errors with codes
+/// in the registry.
+pub struct DiagnosticSink<'a, E> {
+    pub emitter: E,
+    pub codemap: &'a CodeMap,
+    /// The levels that diagnostics are reported at
+    pub lint_levels: LintLevels,
+    /// The explanations of diagnostic codes, which are pointed to by the
+    /// summary
+    pub registry: Option<&'a Registry>,
+    /// The number of errors after which all further diagnostics are ignored
+    pub error_limit: Option<usize>,
+    /// Whether diagnostics are held back until `finish`, and then emitted in
+    /// order of the file and position that they point to
+    pub sort: bool,
+    /// The messages used for the summary, which should usually be the same
+    /// as the catalog used by the emitter
+    pub catalog: Catalog,
+    /// The diagnostics that have been reported, the first `emitted` of which
+    /// have been passed on to the emitter
+    diagnostics: Vec<Diagnostic>,
+    emitted: usize,
+    /// The diagnostics that have been reported, for ignoring duplicates
+    seen: HashSet<Diagnostic>,
+    /// The number of diagnostics ignored after reaching the error limit
+    suppressed: usize,
+}
+
+impl<'a, E: Emitter> DiagnosticSink<'a, E> {
+    pub fn new(emitter: E, codemap: &'a CodeMap) -> DiagnosticSink<'a, E> {
+        DiagnosticSink {
+            emitter,
+            codemap,
+            lint_levels: LintLevels::default(),
+            registry: None,
+            error_limit: None,
+            sort: false,
+            catalog: Catalog::default(),
+            diagnostics: Vec::new(),
+            emitted: 0,
+            seen: HashSet::new(),
+            suppressed: 0,
+        }
+    }
+
+    pub fn with_lint_levels(mut self, lint_levels: LintLevels) -> DiagnosticSink<'a, E> {
+        self.lint_levels = lint_levels;
+        self
+    }
+
+    pub fn with_registry(mut self, registry: &'a Registry) -> DiagnosticSink<'a, E> {
+        self.registry = Some(registry);
+        self
+    }
+
+    pub fn with_error_limit(mut self, error_limit: usize) -> DiagnosticSink<'a, E> {
+        self.error_limit = Some(error_limit);
+        self
+    }
+
+    pub fn with_sorting(mut self, sort: bool) -> DiagnosticSink<'a, E> {
+        self.sort = sort;
+        self
+    }
+
+    pub fn with_catalog(mut self, catalog: Catalog) -> DiagnosticSink<'a, E> {
+        self.catalog = catalog;
+        self
+    }
+
+    /// Report a diagnostic, emitting it straight away unless the diagnostics
+    /// are being sorted
+    pub fn emit(&mut self, diagnostic: Diagnostic) -> io::Result<()> {
+        let diagnostic = match self.lint_levels.apply(diagnostic) {
+            Some(diagnostic) => diagnostic,
+            None => return Ok(()),
+        };
+        if self.is_full() {
+            self.suppressed += 1;
+            return Ok(());
+        }
+        if !self.seen.insert(diagnostic.clone()) {
+            return Ok(());
+        }
+
+        self.diagnostics.push(diagnostic);
+        if !self.sort {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// The number of diagnostics of the given severity that have been
+    /// reported, not counting duplicates or the children of diagnostics
+    pub fn count(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
+    }
+
+    /// The number of errors and bugs that have been reported
+    pub fn error_count(&self) -> usize {
+        self.count(Severity::Bug) + self.count(Severity::Error)
+    }
+
+    /// Whether any errors or bugs have been reported, in which case the tool
+    /// should exit with a failure
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+
+    /// Whether the error limit has been reached, after which any further
+    /// diagnostics are ignored
+    pub fn is_full(&self) -> bool {
+        self.error_limit
+            .is_some_and(|error_limit| self.error_count() >= error_limit)
+    }
+
+    /// Emit any diagnostics that are being held back, followed by a summary
+    /// of the errors and warnings that were reported, and finish the emitter
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.flush()?;
+        if let Some(summary) = self.summary() {
+            self.emitter.emit(self.codemap, &summary)?;
+        }
+        self.emitter.finish()
+    }
+
+    /// A summary of the errors and warnings that have been reported, if
+    /// there were any
+    pub fn summary(&self) -> Option<Diagnostic> {
+        let catalog = &self.catalog;
+        let errors = self.error_count();
+        let warnings = self.count(Severity::Warning);
+        let warnings_emitted = catalog.format_count("warnings-emitted", warnings, &[]);
+
+        let summary = match (errors, warnings) {
+            (0, 0) => return None,
+            (0, _) => Diagnostic::new_warning(warnings_emitted),
+            (errors, warnings) => {
+                let aborting = catalog.format_count("aborting", errors, &[]);
+                match warnings {
+                    0 => Diagnostic::new_error(aborting),
+                    _ => {
+                        let args = [
+                            ("aborting".to_owned(), aborting),
+                            ("warnings".to_owned(), warnings_emitted),
+                        ];
+                        let id = "aborting-with-warnings";
+                        Diagnostic::new_error(
+                            catalog.format(id, &args).unwrap_or_else(|| id.to_owned()),
+                        )
+                    },
+                }
+            },
+        };
+
+        let summary = match self.suppressed {
+            0 => summary,
+            suppressed => {
+                let stopped = catalog.format_count("stopped-after", errors, &[]);
+                let args = [("stopped".to_owned(), stopped)];
+                summary.with_note(catalog.format_count("error-limit", suppressed, &args))
+            },
+        };
+
+        let error_codes = self
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity >= Severity::Error)
+            .filter_map(|diagnostic| diagnostic.code.as_deref());
+        match self
+            .registry
+            .and_then(|registry| registry.hint(catalog, error_codes))
+        {
+            Some(hint) => Some(summary.with_help(hint)),
+            None => Some(summary),
+        }
+    }
+
+    /// Pass the diagnostics that have not been emitted yet on to the emitter
+    fn flush(&mut self) -> io::Result<()> {
+        let pending = &mut self.diagnostics[self.emitted..];
+        if self.sort {
+            // Diagnostics without any labels are kept at the end. The sort is
+            // stable, so diagnostics at the same position stay in the order
+            // that they were reported.
+            pending.sort_by_key(|diagnostic| {
+                let position = position(diagnostic);
+                (position.is_none(), position)
+            });
+        }
+
+        for diagnostic in pending.iter() {
+            self.emitter.emit(self.codemap, diagnostic)?;
+        }
+        self.emitted = self.diagnostics.len();
+        Ok(())
+    }
+}
+
+/// The position that a diagnostic is reported at. Files are laid out one
+/// after the other in a `CodeMap`, so this orders diagnostics by file, and
+/// then by their position within the file.
+fn position(diagnostic: &Diagnostic) -> Option<ByteIndex> {
+    diagnostic
+        .labels
+        .iter()
+        .find(|label| label.style == LabelStyle::Primary)
+        .or_else(|| diagnostic.labels.first())
+        .map(|label| label.span.start())
+}
+
+#[cfg(test)]
+mod tests {
+    use codespan::{CodeMap, Span};
+    use termcolor::NoColor;
+
+    use super::*;
+    use emitter::{Config, DisplayStyle, TermEmitter};
+    use lint::Level;
+    use Label;
+
+    fn sink(
+        codemap: &CodeMap,
+        display_style: DisplayStyle,
+    ) -> DiagnosticSink<'_, TermEmitter<NoColor<Vec<u8>>>> {
+        let config = Config {
+            display_style,
+            ..Config::default()
+        };
+        DiagnosticSink::new(TermEmitter::new(NoColor::new(Vec::new()), config), codemap)
+    }
+
+    fn output(sink: DiagnosticSink<TermEmitter<NoColor<Vec<u8>>>>) -> String {
+        String::from_utf8(sink.emitter.writer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn counts_and_dedupes() {
+        let codemap = CodeMap::new();
+        let mut sink = sink(&codemap, DisplayStyle::Short);
+        sink.emit(Diagnostic::new_warning("unused")).unwrap();
+        sink.emit(Diagnostic::new_error("oops")).unwrap();
+        sink.emit(Diagnostic::new_warning("unused")).unwrap();
+        sink.emit(Diagnostic::new_warning("unused").with_code("W1"))
+            .unwrap();
+        sink.emit(Diagnostic::new_bug("ouch")).unwrap();
+        sink.finish().unwrap();
+
+        assert_eq!(sink.count(Severity::Warning), 2);
+        assert_eq!(sink.error_count(), 2);
+        assert!(sink.has_errors());
+        assert_eq!(
+            output(sink),
+            "\
+warning: unused
+error: oops
+warning[W1]: unused
+error: internal compiler error: ouch
+error: aborting due to 2 previous errors; 2 warnings emitted
+",
+        );
+    }
+
+    #[test]
+    fn lint_levels() {
+        let codemap = CodeMap::new();
+        let lint_levels = LintLevels::new()
+            .with_level("W1", Level::Allow)
+            .with_warnings_as_errors(true);
+        let mut sink = sink(&codemap, DisplayStyle::Short).with_lint_levels(lint_levels);
+        sink.emit(Diagnostic::new_warning("allowed").with_code("W1"))
+            .unwrap();
+        sink.emit(Diagnostic::new_warning("denied").with_code("W2"))
+            .unwrap();
+        sink.finish().unwrap();
+
+        assert_eq!(
+            output(sink),
+            "error[W2]: denied\nerror: aborting due to previous error\n",
+        );
+    }
+
+    #[test]
+    fn explain_hint() {
+        let codemap = CodeMap::new();
+        let registry = Registry::new()
+            .with_explanation("E1", "")
+            .with_explanation("W1", "");
+        let mut sink = sink(&codemap, DisplayStyle::Rich).with_registry(&registry);
+        sink.emit(Diagnostic::new_warning("unused").with_code("W1"))
+            .unwrap();
+        sink.emit(Diagnostic::new_error("oops").with_code("E1"))
+            .unwrap();
+        sink.emit(Diagnostic::new_error("unexplained").with_code("E2"))
+            .unwrap();
+        sink.finish().unwrap();
+
+        assert_eq!(
+            output(sink),
+            "\
+warning[W1]: unused
+error[E1]: oops
+error[E2]: unexplained
+error: aborting due to 2 previous errors; 1 warning emitted
+ = help: For more information about this error, try `--explain E1`.
+",
+        );
+    }
+
+    #[test]
+    fn error_limit() {
+        let codemap = CodeMap::new();
+        let mut sink = sink(&codemap, DisplayStyle::Rich).with_error_limit(2);
+        for i in 0..4 {
+            sink.emit(Diagnostic::new_error(format!("error {}", i)))
+                .unwrap();
+            sink.emit(Diagnostic::new_note(format!("note {}", i)))
+                .unwrap();
+        }
+        assert!(sink.is_full());
+        sink.finish().unwrap();
+
+        assert_eq!(
+            output(sink),
+            "\
+error: error 0
+note: note 0
+error: error 1
+error: aborting due to 2 previous errors
+ = note: stopped after 2 errors, so 5 more diagnostics were not shown
+",
+        );
+    }
+
+    #[test]
+    fn sorted_by_position() {
+        let mut codemap = CodeMap::new();
+        let a = codemap.add_filemap("a".into(), "foo\nbar\n".to_string());
+        let b = codemap.add_filemap("b".into(), "baz\n".to_string());
+        let label = |start| Label::new_primary(Span::from_offset(start, 3.into()));
+
+        let mut sink = sink(&codemap, DisplayStyle::Short).with_sorting(true);
+        sink.emit(Diagnostic::new_warning("no location")).unwrap();
+        sink.emit(Diagnostic::new_warning("baz").with_label(label(b.span().start())))
+            .unwrap();
+        let bar = a.byte_index(1.into(), 0.into()).unwrap();
+        sink.emit(Diagnostic::new_warning("bar").with_label(label(bar)))
+            .unwrap();
+        sink.emit(Diagnostic::new_warning("foo").with_label(label(a.span().start())))
+            .unwrap();
+        sink.finish().unwrap();
+
+        assert_eq!(
+            output(sink),
+            "\
+<a>:1:1: warning: foo
+<a>:2:1: warning: bar
+<b>:1:1: warning: baz
+warning: no location
+warning: 4 warnings emitted
+",
+        );
+    }
+}
@@ -0,0 +1,849 @@
+//! Layout of the annotated source snippets that are shown for each file
+//! referenced by a diagnostic
+//!
+//! A snippet is laid out independently of how it is finally drawn, as a list
+//! of rows made up of styled segments of text.
+
+use codespan::{ByteIndex, FileMap, LineIndex, LineOffset, RawIndex, RawOffset, Span};
+use std::{cmp, iter};
+use unicode_width::UnicodeWidthChar;
+
+use emitter::{Config, Glyphs};
+use {Label, LabelStyle, Suggestion};
+
+/// A run of text drawn in a single style
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    /// The text to draw
+    pub text: String,
+    /// The style of the label that this text belongs to, if any
+    pub style: Option<LabelStyle>,
+}
+
+/// A row in a snippet
+#[derive(Clone, Debug, PartialEq)]
+pub enum Row {
+    /// A line of source code, prefixed with the gutter for multi-line labels
+    Source(LineIndex, Vec<Segment>),
+    /// A line of source code added by a suggestion, which has no line number
+    Added(Vec<Segment>),
+    /// Underlines, connectors and messages drawn beneath a line of source
+    Annotation(Vec<Segment>),
+    /// Marks a gap between lines that are too far apart to be shown together
+    Elision,
+}
+
+/// The annotated source code for the labels in a single file
+#[derive(Clone, Debug)]
+pub struct Snippet<'a> {
+    /// The file that the labels point into
+    pub file: &'a FileMap,
+    /// The location that the snippet should be reported at
+    pub origin: ByteIndex,
+    /// The rows of the snippet
+    pub rows: Vec<Row>,
+}
+
+impl<'a> Snippet<'a> {
+    /// Lay out the given labels, which must all point into `file`, showing
+    /// `config.context_lines` lines of source before and after each of them
+    pub fn new(file: &'a FileMap, labels: &[&'a Label], config: &Config) -> Snippet<'a> {
+        let mut labels = labels
+            .iter()
+            .map(|label| LabelLayout::new(file, label, config.tab_width))
+            .collect::<Vec<_>>();
+        labels.sort_by_key(|layout| (layout.label.span.start(), layout.label.span.end()));
+
+        let origin = labels
+            .iter()
+            .find(|layout| layout.label.style == LabelStyle::Primary)
+            .or_else(|| labels.first())
+            .map_or(file.span().start(), |layout| layout.label.span.start());
+
+        let depths = assign_depths(&mut labels);
+        let ranges = context_ranges(file, &labels, config.context_lines);
+        let max_columns = max_columns(config, ranges.last().map(|&(_, end)| end));
+        let mut rows = Vec::new();
+
+        for (i, &(start, end)) in ranges.iter().enumerate() {
+            if i > 0 {
+                rows.push(Row::Elision);
+            }
+            for line in start.0..end.0 + 1 {
+                let line = LineIndex(line);
+                layout_line(file, &labels, depths, line, config, max_columns, &mut rows);
+            }
+        }
+
+        Snippet { file, origin, rows }
+    }
+
+    /// Lay out the source code of `file` as it would look with the suggestion
+    /// applied, marking additions with `+` and replacements with `~`.
+    /// Deletions are marked with `-` under the original source code. Lines
+    /// past the end of the replaced source are shown without line numbers.
+    pub fn suggestion(file: &'a FileMap, suggestion: &Suggestion, config: &Config) -> Snippet<'a> {
+        let span = suggestion.span;
+        let start_line = file.find_line(span.start()).expect("start_line");
+        let end_line = file.find_line(span.end()).expect("end_line");
+        let prefix = file
+            .src_slice(Span::new(line_start(file, start_line), span.start()))
+            .expect("prefix");
+        let suffix = &line_src(file, end_line)[cmp::min(
+            (span.end() - line_start(file, end_line)).to_usize(),
+            line_src(file, end_line).len(),
+        )..];
+
+        let mut rows = Vec::new();
+        let style = Some(LabelStyle::Primary);
+        let max_columns = max_columns(config, Some(cmp::max(start_line, end_line)));
+
+        if suggestion.replacement.is_empty() {
+            for line in start_line.0..end_line.0 + 1 {
+                let line = LineIndex(line);
+                let src = line_src(file, line);
+                let start = if line == start_line { prefix.len() } else { 0 };
+                let end = if line == end_line {
+                    src.len() - suffix.len()
+                } else {
+                    src.len()
+                };
+                let start = display_column(src, start, config.tab_width);
+                let end = display_column(src, end, config.tab_width);
+                let first_row = rows.len();
+
+                let width = display_column(src, src.len(), config.tab_width);
+                let window = max_columns
+                    .and_then(|max_columns| window(width, (start, end), (start, end), max_columns));
+
+                rows.push(Row::Source(
+                    line,
+                    segments(styled_chars(src, config.tab_width, |_| None)),
+                ));
+                let mut grid = Grid::new();
+                for column in start..clip(cmp::max(end, start + 1), window) {
+                    grid.put(column, config.glyphs.secondary_underline, style);
+                }
+                rows.push(Row::Annotation(grid.into_segments()));
+
+                if let Some(window) = window {
+                    truncate(&mut rows[first_row..], 0, width, window);
+                }
+            }
+        } else {
+            let mark = if span.start() == span.end() { '+' } else { '~' };
+            let patched = format!("{}{}{}", prefix, suggestion.replacement, suffix);
+            // Every line break is part of the replacement, as the prefix and
+            // suffix are taken from single lines
+            let patched_lines = patched
+                .split('\n')
+                .map(|line| line.trim_end_matches('\r'))
+                .collect::<Vec<_>>();
+
+            for (i, src) in patched_lines.iter().enumerate() {
+                let start = if i == 0 { prefix.len() } else { 0 };
+                let end = if i + 1 == patched_lines.len() {
+                    src.len() - suffix.len()
+                } else {
+                    src.len()
+                };
+                let start = display_column(src, start, config.tab_width);
+                let end = display_column(src, end, config.tab_width);
+                // Lines that end in an inserted line break are marked, even
+                // if nothing else was inserted on them
+                let end = if i + 1 < patched_lines.len() {
+                    cmp::max(end, start + 1)
+                } else {
+                    end
+                };
+                let first_row = rows.len();
+                let width = display_column(src, src.len(), config.tab_width);
+                let window = max_columns
+                    .and_then(|max_columns| window(width, (start, end), (start, end), max_columns));
+
+                let source = styled_chars(src, config.tab_width, |column| {
+                    let is_marked = start <= column && column < end;
+                    if is_marked {
+                        style
+                    } else {
+                        None
+                    }
+                });
+                let line = start_line + LineOffset(i as RawOffset);
+                rows.push(match line {
+                    _ if line > end_line => Row::Added(segments(source)),
+                    line => Row::Source(line, segments(source)),
+                });
+
+                // Lines that only hold the source after the replacement have
+                // nothing to mark
+                if start < end {
+                    let mut grid = Grid::new();
+                    for column in start..clip(end, window) {
+                        grid.put(column, mark, style);
+                    }
+                    rows.push(Row::Annotation(grid.into_segments()));
+                }
+
+                if let Some(window) = window {
+                    truncate(&mut rows[first_row..], 0, width, window);
+                }
+            }
+        }
+
+        Snippet {
+            file,
+            origin: span.start(),
+            rows,
+        }
+    }
+}
+
+/// The resolved location of a label within a file
+#[derive(Clone)]
+struct LabelLayout<'a> {
+    label: &'a Label,
+    start_line: LineIndex,
+    /// The display column of the start of the label within its line
+    start_column: usize,
+    end_line: LineIndex,
+    /// The display column of the end of the label within its line
+    end_column: usize,
+    /// Whether the label begins at the start of a line, ignoring whitespace.
+    /// Multi-line labels that do can be pointed to directly from the gutter.
+    starts_line: bool,
+    /// The gutter column used for drawing multi-line labels
+    depth: usize,
+}
+
+impl<'a> LabelLayout<'a> {
+    fn new(file: &FileMap, label: &'a Label, tab_width: usize) -> LabelLayout<'a> {
+        let start_line = file.find_line(label.span.start()).expect("start_line");
+        let start_column = (label.span.start() - line_start(file, start_line)).to_usize();
+        let start_src = line_src(file, start_line);
+
+        let end_line = file.find_line(label.span.end()).expect("end_line");
+        let (end_line, end_column) =
+            match (label.span.end() - line_start(file, end_line)).to_usize() {
+                // Spans that finish just after a newline should be considered as
+                // ending on the previous line
+                0 if end_line > start_line => {
+                    let end_line = end_line - LineOffset(1);
+                    (end_line, line_src(file, end_line).len())
+                },
+                end_column => (end_line, end_column),
+            };
+
+        let end_src = line_src(file, end_line);
+
+        LabelLayout {
+            label,
+            start_line,
+            start_column: display_column(start_src, start_column, tab_width),
+            end_line,
+            end_column: display_column(end_src, end_column.min(end_src.len()), tab_width),
+            starts_line: start_src
+                .get(..start_column)
+                .is_some_and(|prefix| prefix.trim().is_empty()),
+            depth: 0,
+        }
+    }
+
+    fn is_multiline(&self) -> bool {
+        self.start_line != self.end_line
+    }
+
+    /// The number of columns covered by the underline of a single-line label
+    fn width(&self) -> usize {
+        (self.end_column.saturating_sub(self.start_column)).max(1)
+    }
+
+    fn mark(&self, glyphs: &Glyphs) -> char {
+        match self.label.style {
+            LabelStyle::Primary => glyphs.primary_underline,
+            LabelStyle::Secondary => glyphs.secondary_underline,
+        }
+    }
+}
+
+/// The number of columns taken up by a character displayed at `column`.
+/// East Asian wide characters take up two columns, and combining marks and
+/// control characters take up none. Tab widths of 0 are treated as 1.
+fn char_width(ch: char, column: usize, tab_width: usize) -> usize {
+    match ch {
+        '\t' => tab_width.max(1) - column % tab_width.max(1),
+        _ => UnicodeWidthChar::width(ch).unwrap_or(0),
+    }
+}
+
+/// The display column of the character at the byte offset `index` in `src`,
+/// with tabs expanded to the next multiple of `tab_width`
+fn display_column(src: &str, index: usize, tab_width: usize) -> usize {
+    src.char_indices()
+        .take_while(|&(i, _)| i < index)
+        .fold(0, |column, (_, ch)| {
+            column + char_width(ch, column, tab_width)
+        })
+}
+
+/// The characters of `src` as they are displayed, with tabs expanded to
+/// spaces. Each character is styled according to the column that it is
+/// displayed at, except for zero-width characters, which take the style of
+/// the character that they follow.
+fn styled_chars<F>(src: &str, tab_width: usize, style_at: F) -> Vec<(char, Option<LabelStyle>)>
+where
+    F: Fn(usize) -> Option<LabelStyle>,
+{
+    let mut chars = Vec::with_capacity(src.len());
+    let mut column = 0;
+    let mut style = None;
+
+    for ch in src.chars() {
+        let width = char_width(ch, column, tab_width);
+        match ch {
+            '\t' => chars.extend((column..column + width).map(|column| (' ', style_at(column)))),
+            _ => {
+                if width > 0 {
+                    style = style_at(column);
+                }
+                chars.push((ch, style));
+            },
+        }
+        column += width;
+    }
+
+    chars
+}
+
+fn line_start(file: &FileMap, line: LineIndex) -> ByteIndex {
+    file.line_byte_index(line).expect("line_start")
+}
+
+/// The source of a line, without the trailing newline
+fn line_src(file: &FileMap, line: LineIndex) -> &str {
+    let line_span = file.line_span(line).expect("line_span");
+    file.src_slice(line_span)
+        .expect("line_src")
+        .trim_end_matches(['\r', '\n'])
+}
+
+/// The ranges of lines to show for the given labels, padded with
+/// `context_lines` lines on either side. Ranges that overlap or are next to
+/// each other are merged.
+fn context_ranges(
+    file: &FileMap,
+    labels: &[LabelLayout],
+    context_lines: usize,
+) -> Vec<(LineIndex, LineIndex)> {
+    let context_lines = context_lines as RawIndex;
+    let last_line = {
+        let last_line = file.find_line(file.span().end()).expect("last_line");
+        // Don't show the empty line following a trailing newline as context
+        match last_line.0 {
+            0 => last_line,
+            _ if line_src(file, last_line).is_empty() => last_line - LineOffset(1),
+            _ => last_line,
+        }
+    };
+
+    let mut ranges = labels
+        .iter()
+        .map(|layout| {
+            let start = layout.start_line.0.saturating_sub(context_lines);
+            let end = cmp::min(
+                layout.end_line.0.saturating_add(context_lines),
+                cmp::max(last_line.0, layout.end_line.0),
+            );
+            (LineIndex(start), LineIndex(end))
+        })
+        .collect::<Vec<_>>();
+    ranges.sort();
+
+    let mut merged: Vec<(LineIndex, LineIndex)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(&mut (_, ref mut last_end)) if start.0 <= last_end.0 + 1 => {
+                *last_end = cmp::max(*last_end, end);
+                continue;
+            },
+            _ => {},
+        }
+        merged.push((start, end));
+    }
+    merged
+}
+
+/// Assign each multi-line label a column in the gutter, such that labels that
+/// overlap never share a column. Returns the number of columns needed.
+fn assign_depths(labels: &mut [LabelLayout]) -> usize {
+    let mut depth_ends: Vec<LineIndex> = Vec::new();
+
+    for layout in labels.iter_mut().filter(|layout| layout.is_multiline()) {
+        match depth_ends.iter().position(|&end| end < layout.start_line) {
+            Some(depth) => {
+                depth_ends[depth] = layout.end_line;
+                layout.depth = depth;
+            },
+            None => {
+                layout.depth = depth_ends.len();
+                depth_ends.push(layout.end_line);
+            },
+        }
+    }
+
+    depth_ends.len()
+}
+
+/// A grid of styled characters, used for building up annotation rows
+struct Grid {
+    cells: Vec<(char, Option<LabelStyle>)>,
+}
+
+impl Grid {
+    fn new() -> Grid {
+        Grid { cells: Vec::new() }
+    }
+
+    fn put(&mut self, column: usize, ch: char, style: Option<LabelStyle>) {
+        if self.cells.len() <= column {
+            self.cells.resize(column + 1, (' ', None));
+        }
+        self.cells[column] = (ch, style);
+    }
+
+    fn put_str(&mut self, column: usize, text: &str, style: Option<LabelStyle>) {
+        for (i, ch) in text.chars().enumerate() {
+            self.put(column + i, ch, style);
+        }
+    }
+
+    fn into_segments(mut self) -> Vec<Segment> {
+        while self.cells.last().is_some_and(|&(ch, _)| ch == ' ') {
+            self.cells.pop();
+        }
+        segments(self.cells)
+    }
+}
+
+/// The number of columns left for source code when lines are limited to
+/// `config.max_width`, once room has been made for line numbers up to
+/// `last_line` and the gutter beside them
+fn max_columns(config: &Config, last_line: Option<LineIndex>) -> Option<usize> {
+    config.max_width.map(|max_width| {
+        let gutter = match last_line {
+            Some(line) if config.show_line_numbers => (line.0 + 1).to_string().len() + 3,
+            _ => 2,
+        };
+        max_width.saturating_sub(gutter)
+    })
+}
+
+const ELLIPSIS: &str = "...";
+
+/// The columns of a line of source that is `width` columns wide that are
+/// shown when it is trimmed to fit within `max_columns`, or `None` if the
+/// line already fits.
+///
+/// The columns between `start` and `end` are centered in the window if they
+/// fit, leaving room for an ellipsis on either side. Otherwise the window is
+/// centered on the columns of `focus`, which are usually those of the
+/// primary label.
+fn window(
+    width: usize,
+    (start, end): (usize, usize),
+    focus: (usize, usize),
+    max_columns: usize,
+) -> Option<(usize, usize)> {
+    if width <= max_columns {
+        return None;
+    }
+
+    let visible = cmp::max(max_columns.saturating_sub(2 * ELLIPSIS.len()), 1);
+    let (start, end) = match visible.checked_sub(end.saturating_sub(start)) {
+        Some(_) => (start, end),
+        None => focus,
+    };
+    let window_start = match visible.checked_sub(end.saturating_sub(start)) {
+        Some(slack) => cmp::min(start.saturating_sub(slack / 2), width - visible),
+        None => start,
+    };
+    Some((window_start, cmp::min(window_start + visible, width)))
+}
+
+/// Clip the end of an annotation to the end of `window`, if the line it is
+/// drawn under is trimmed
+fn clip(end: usize, window: Option<(usize, usize)>) -> usize {
+    match window {
+        Some((_, window_end)) => cmp::min(end, window_end),
+        None => end,
+    }
+}
+
+/// Trim the rows laid out for a line of source that is `width` columns wide
+/// to the columns between `window_start` and `window_end`. The first row
+/// must be the source row, and the rest of the rows its annotations, which
+/// should already have been clipped to `window_end`.
+///
+/// Hidden source code is replaced with `...`, and the annotations are
+/// shifted so that they stay aligned with the source. The first `offset`
+/// columns of each row hold the gutter for multi-line labels, and are kept
+/// as they are.
+fn truncate(
+    rows: &mut [Row],
+    offset: usize,
+    width: usize,
+    (window_start, window_end): (usize, usize),
+) {
+    for (i, row) in rows.iter_mut().enumerate() {
+        let is_source = i == 0;
+        let row_segments = match *row {
+            Row::Source(_, ref mut segments)
+            | Row::Added(ref mut segments)
+            | Row::Annotation(ref mut segments) => segments,
+            Row::Elision => continue,
+        };
+
+        let mut gutter = Vec::new();
+        let mut hidden = None;
+        let mut shown = Vec::new();
+        let mut column = 0;
+        for segment in row_segments.iter() {
+            for ch in segment.text.chars() {
+                let ch_width = UnicodeWidthChar::width(ch).unwrap_or(0);
+                if column < offset {
+                    gutter.push((ch, segment.style));
+                } else if column < offset + window_start {
+                    hidden = Some((ch, segment.style));
+                    // Keep the visible half of a wide character as padding
+                    for _ in (offset + window_start)..(column + ch_width) {
+                        shown.push((' ', segment.style));
+                    }
+                } else if !is_source || column + ch_width <= offset + window_end {
+                    shown.push((ch, segment.style));
+                }
+                column += ch_width;
+            }
+        }
+
+        if window_start > 0 && !shown.is_empty() {
+            gutter.resize(offset, (' ', None));
+            match hidden {
+                _ if is_source => gutter.extend(ELLIPSIS.chars().map(|ch| (ch, None))),
+                // Continue runs of underlines and connectors into the
+                // space taken by the ellipsis
+                Some(cell) if shown.first() == Some(&cell) => {
+                    gutter.extend(iter::repeat_n(cell, ELLIPSIS.len()))
+                },
+                _ => gutter.extend(iter::repeat_n((' ', None), ELLIPSIS.len())),
+            }
+        }
+        gutter.extend(shown);
+        if is_source && window_end < width {
+            gutter.extend(ELLIPSIS.chars().map(|ch| (ch, None)));
+        }
+
+        *row_segments = segments(gutter);
+    }
+}
+
+/// Group consecutive characters of the same style into segments
+fn segments<I>(cells: I) -> Vec<Segment>
+where
+    I: IntoIterator<Item = (char, Option<LabelStyle>)>,
+{
+    let mut segments: Vec<Segment> = Vec::new();
+    for (ch, style) in cells {
+        match segments.last_mut() {
+            Some(ref mut segment) if segment.style == style => {
+                segment.text.push(ch);
+                continue;
+            },
+            _ => {},
+        }
+        segments.push(Segment {
+            text: ch.to_string(),
+            style,
+        });
+    }
+    segments
+}
+
+/// Lay out the source and annotations of a single line
+fn layout_line(
+    file: &FileMap,
+    labels: &[LabelLayout],
+    depths: usize,
+    line: LineIndex,
+    config: &Config,
+    max_columns: Option<usize>,
+    rows: &mut Vec<Row>,
+) {
+    let glyphs = &config.glyphs;
+    let first_row = rows.len();
+    let src = line_src(file, line);
+    // The gutter for multi-line labels, followed by a space
+    let offset = if depths == 0 { 0 } else { depths + 1 };
+
+    let multilines = labels
+        .iter()
+        .filter(|layout| layout.is_multiline())
+        .collect::<Vec<_>>();
+    let width = display_column(src, src.len(), config.tab_width);
+    let window = max_columns.and_then(|max_columns| {
+        let labelled = line_columns(labels.iter(), line).unwrap_or((0, 0));
+        let primary = labels
+            .iter()
+            .filter(|layout| layout.label.style == LabelStyle::Primary);
+        let focus = line_columns(primary, line).unwrap_or(labelled);
+        window(width, labelled, focus, max_columns.saturating_sub(offset))
+    });
+
+    // Single-line labels are clipped to the columns that are shown, so that
+    // no underlines or messages are drawn beneath hidden source code
+    let clipped = labels
+        .iter()
+        .filter(|layout| !layout.is_multiline() && layout.start_line == line)
+        .filter_map(|layout| match window {
+            Some((start, end))
+                if layout.start_column >= end || layout.start_column + layout.width() <= start =>
+            {
+                None
+            },
+            Some((_, end)) => Some(LabelLayout {
+                end_column: cmp::min(layout.end_column, end),
+                ..layout.clone()
+            }),
+            None => Some(layout.clone()),
+        })
+        .collect::<Vec<_>>();
+    let mut singles = clipped.iter().collect::<Vec<_>>();
+    singles.sort_by_key(|layout| (layout.start_column, layout.end_column));
+    // Multi-line labels starting or ending in hidden source code point to
+    // the last column that is shown
+    let mark_column = |column: usize| match window {
+        Some((_, end)) => cmp::min(column, end - 1),
+        None => column,
+    };
+
+    // Source row
+
+    let mut gutter = Grid::new();
+    for layout in &multilines {
+        if layout.start_line == line && layout.starts_line {
+            gutter.put(
+                layout.depth,
+                glyphs.multiline_start,
+                Some(layout.label.style),
+            );
+        } else if layout.start_line < line && line <= layout.end_line {
+            gutter.put(layout.depth, glyphs.vertical, Some(layout.label.style));
+        }
+    }
+    if depths > 0 {
+        gutter.put(depths, ' ', None);
+    }
+    let mut source = gutter.cells;
+
+    let mut styles = vec![None; display_column(src, src.len(), config.tab_width)];
+    for &style in &[LabelStyle::Secondary, LabelStyle::Primary] {
+        for layout in singles.iter().filter(|layout| layout.label.style == style) {
+            let end = cmp::min(layout.end_column, styles.len());
+            for column_style in &mut styles[cmp::min(layout.start_column, end)..end] {
+                *column_style = Some(style);
+            }
+        }
+    }
+    source.extend(styled_chars(src, config.tab_width, |column| {
+        styles.get(column).cloned().unwrap_or(None)
+    }));
+    rows.push(Row::Source(line, segments(source)));
+
+    // The verticals of multi-line labels that continue past this line
+    let mut active = multilines
+        .iter()
+        .filter(|layout| {
+            (layout.start_line < line && line <= layout.end_line)
+                || (layout.start_line == line && layout.starts_line)
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    let new_grid = |active: &[&LabelLayout]| {
+        let mut grid = Grid::new();
+        for layout in active {
+            grid.put(layout.depth, glyphs.vertical, Some(layout.label.style));
+        }
+        grid
+    };
+
+    // Underlines for single-line labels, stacked so that overlapping or
+    // adjacent underlines are drawn on separate rows
+
+    let mut mark_rows: Vec<Vec<&LabelLayout>> = Vec::new();
+    for &layout in &singles {
+        let conflicts = |other: &&LabelLayout| {
+            layout.start_column <= other.start_column + other.width()
+                && other.start_column <= layout.start_column + layout.width()
+        };
+        match mark_rows.iter().position(|row| !row.iter().any(&conflicts)) {
+            Some(i) => mark_rows[i].push(layout),
+            None => mark_rows.push(vec![layout]),
+        }
+    }
+
+    // Labels that have their message hanging off a vertical connector
+    let mut hanging: Vec<&LabelLayout> = Vec::new();
+    for row in &mark_rows {
+        let mut grid = new_grid(&active);
+        for layout in &hanging {
+            grid.put(
+                offset + layout.start_column,
+                glyphs.vertical,
+                Some(layout.label.style),
+            );
+        }
+        for layout in row {
+            for i in 0..layout.width() {
+                grid.put(
+                    offset + layout.start_column + i,
+                    layout.mark(glyphs),
+                    Some(layout.label.style),
+                );
+            }
+        }
+
+        // Only the rightmost label in a row can have its message inline, and
+        // only if no connectors from the rows above would cross it
+        let last = row.len() - 1;
+        let connectors_end = hanging.iter().map(|other| other.start_column + 1).max();
+        for (i, &layout) in row.iter().enumerate() {
+            let message = match layout.label.message {
+                Some(ref message) => message,
+                None => continue,
+            };
+            let end = layout.start_column + layout.width();
+            if i == last && connectors_end.is_none_or(|connectors_end| connectors_end <= end) {
+                grid.put_str(offset + end + 1, message, Some(layout.label.style));
+            } else {
+                hanging.push(layout);
+            }
+        }
+
+        rows.push(Row::Annotation(grid.into_segments()));
+    }
+
+    if !hanging.is_empty() {
+        hanging.sort_by_key(|layout| layout.start_column);
+
+        let mut grid = new_grid(&active);
+        for layout in &hanging {
+            grid.put(
+                offset + layout.start_column,
+                glyphs.vertical,
+                Some(layout.label.style),
+            );
+        }
+        rows.push(Row::Annotation(grid.into_segments()));
+
+        while let Some(layout) = hanging.pop() {
+            let mut grid = new_grid(&active);
+            for other in &hanging {
+                grid.put(
+                    offset + other.start_column,
+                    glyphs.vertical,
+                    Some(other.label.style),
+                );
+            }
+            if let Some(ref message) = layout.label.message {
+                grid.put_str(
+                    offset + layout.start_column,
+                    message,
+                    Some(layout.label.style),
+                );
+            }
+            rows.push(Row::Annotation(grid.into_segments()));
+        }
+    }
+
+    // Multi-line labels starting partway through this line:
+    //
+    // ```text
+    //   |  _________^
+    // ```
+
+    let mut starts = multilines
+        .iter()
+        .filter(|layout| layout.start_line == line && !layout.starts_line)
+        .cloned()
+        .collect::<Vec<_>>();
+    starts.sort_by_key(|layout| layout.depth);
+
+    for layout in starts {
+        let style = Some(layout.label.style);
+        let mut grid = Grid::new();
+        grid.put(layout.depth, glyphs.multiline_top_left, style);
+        let start = offset + mark_column(layout.start_column);
+        for column in (layout.depth + 1)..start {
+            grid.put(column, glyphs.horizontal, style);
+        }
+        grid.put(start, layout.mark(glyphs), style);
+        for other in &active {
+            grid.put(other.depth, glyphs.vertical, Some(other.label.style));
+        }
+        rows.push(Row::Annotation(grid.into_segments()));
+        active.push(layout);
+    }
+
+    // Multi-line labels ending on this line, with their messages:
+    //
+    // ```text
+    //   | |_________^ message
+    // ```
+
+    let mut ends = multilines
+        .iter()
+        .filter(|layout| layout.end_line == line)
+        .cloned()
+        .collect::<Vec<_>>();
+    ends.sort_by_key(|layout| layout.depth);
+
+    while let Some(layout) = ends.pop() {
+        active.retain(|other| other.depth != layout.depth);
+
+        let style = Some(layout.label.style);
+        let end = offset + mark_column(layout.end_column.saturating_sub(1));
+        let mut grid = Grid::new();
+        grid.put(layout.depth, glyphs.multiline_bottom_left, style);
+        for column in (layout.depth + 1)..end {
+            grid.put(column, glyphs.horizontal, style);
+        }
+        grid.put(end.max(layout.depth + 1), layout.mark(glyphs), style);
+        if let Some(ref message) = layout.label.message {
+            grid.put_str(end.max(layout.depth + 1) + 2, message, style);
+        }
+        for other in &active {
+            grid.put(other.depth, glyphs.vertical, Some(other.label.style));
+        }
+        rows.push(Row::Annotation(grid.into_segments()));
+    }
+
+    if let Some(window) = window {
+        truncate(&mut rows[first_row..], offset, width, window);
+    }
+}
+
+/// The range of columns covered by the starts and ends of `labels` on `line`
+fn line_columns<'a, I>(labels: I, line: LineIndex) -> Option<(usize, usize)>
+where
+    I: Iterator<Item = &'a LabelLayout<'a>>,
+{
+    let columns = labels.flat_map(|layout| {
+        let start = Some(layout.start_column).filter(|_| layout.start_line == line);
+        let end = Some(layout.end_column).filter(|_| layout.end_line == line);
+        start.into_iter().chain(end)
+    });
+    columns.fold(None, |range, column| {
+        Some(range.map_or((column, column), |(start, end)| {
+            (cmp::min(start, column), cmp::max(end, column))
+        }))
+    })
+}
@@ -0,0 +1,340 @@
+//! Color themes for diagnostics rendered by `emit`, and explanations
+//! rendered by `emit_explanation`
+
+use std::str::FromStr;
+use termcolor::{Color, ColorSpec};
+
+use {LabelStyle, Severity};
+
+/// The colors and text attributes used when rendering diagnostics
+///
+/// A theme can be loaded from a string in the same format as `GCC_COLORS`,
+/// made up of `name=SGR` entries separated by colons. Each name is one of the
+/// fields of `Styles` with underscores replaced by dashes, and each value is a
+/// list of [SGR parameters] separated by semicolons. Styles that are not
+/// mentioned keep their default:
+///
+/// ```rust
+/// use codespan_reporting::Styles;
+///
+/// let styles = "header-warning=01;35:primary-label-warning=35:gutter=02"
+///     .parse::<Styles>()
+///     .unwrap();
+/// ```
+///
+/// The supported parameters are `0` (reset), `1` (bold), `2` (dim, which
+/// turns off `intense`), `4` (underline), `30`–`37` and `90`–`97`
+/// (foreground), `40`–`47` and `100`–`107` (background), `39` and `49`
+/// (default colors), and the extended colors `38;5;N` and `38;2;R;G;B`.
+///
+/// [SGR parameters]: https://en.wikipedia.org/wiki/ANSI_escape_code#SGR_(Select_Graphic_Rendition)_parameters
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Styles {
+    /// The severity of a diagnostic with `Severity::Bug`, and its code
+    pub header_bug: ColorSpec,
+    /// The severity of a diagnostic with `Severity::Error`, and its code
+    pub header_error: ColorSpec,
+    /// The severity of a diagnostic with `Severity::Warning`, and its code
+    pub header_warning: ColorSpec,
+    /// The severity of a diagnostic with `Severity::Note`, and its code
+    pub header_note: ColorSpec,
+    /// The severity of a diagnostic with `Severity::Help`, and its code
+    pub header_help: ColorSpec,
+    /// The message of a diagnostic
+    pub header_message: ColorSpec,
+
+    /// The primary labels of a diagnostic with `Severity::Bug`
+    pub primary_label_bug: ColorSpec,
+    /// The primary labels of a diagnostic with `Severity::Error`
+    pub primary_label_error: ColorSpec,
+    /// The primary labels of a diagnostic with `Severity::Warning`
+    pub primary_label_warning: ColorSpec,
+    /// The primary labels of a diagnostic with `Severity::Note`
+    pub primary_label_note: ColorSpec,
+    /// The primary labels of a diagnostic with `Severity::Help`, and the
+    /// changes shown for suggestions
+    pub primary_label_help: ColorSpec,
+    /// Secondary labels
+    pub secondary_label: ColorSpec,
+
+    /// Line numbers, the border beside the source code, and the bullets
+    /// before notes
+    pub gutter: ColorSpec,
+    /// The severity of a `note:` shown beneath the snippets
+    pub note: ColorSpec,
+    /// The severity of a `help:` shown beneath the snippets
+    pub help: ColorSpec,
+
+    /// Headings in explanations shown by `emit_explanation`
+    pub heading: ColorSpec,
+    /// Code in explanations
+    pub code: ColorSpec,
+    /// Emphasised text in explanations
+    pub emphasis: ColorSpec,
+}
+
+impl Styles {
+    /// The style of the header of a diagnostic with the given severity
+    pub fn header(&self, severity: Severity) -> &ColorSpec {
+        match severity {
+            Severity::Bug => &self.header_bug,
+            Severity::Error => &self.header_error,
+            Severity::Warning => &self.header_warning,
+            Severity::Note => &self.header_note,
+            Severity::Help => &self.header_help,
+        }
+    }
+
+    /// The style of a label in a diagnostic with the given severity
+    pub fn label(&self, severity: Severity, style: LabelStyle) -> &ColorSpec {
+        match (style, severity) {
+            (LabelStyle::Primary, Severity::Bug) => &self.primary_label_bug,
+            (LabelStyle::Primary, Severity::Error) => &self.primary_label_error,
+            (LabelStyle::Primary, Severity::Warning) => &self.primary_label_warning,
+            (LabelStyle::Primary, Severity::Note) => &self.primary_label_note,
+            (LabelStyle::Primary, Severity::Help) => &self.primary_label_help,
+            (LabelStyle::Secondary, _) => &self.secondary_label,
+        }
+    }
+
+    /// The style of the severity of a note shown beneath the snippets
+    pub fn footer(&self, severity: Severity) -> &ColorSpec {
+        match severity {
+            Severity::Note => &self.note,
+            Severity::Help => &self.help,
+            Severity::Bug | Severity::Error | Severity::Warning => self.header(severity),
+        }
+    }
+
+    /// Update the styles named in a `GCC_COLORS`-like string, leaving the
+    /// rest unchanged
+    pub fn apply(&mut self, spec: &str) -> Result<(), String> {
+        for entry in spec.split(':').filter(|entry| !entry.is_empty()) {
+            let (name, sgr) = match entry.find('=') {
+                Some(i) => (&entry[..i], &entry[i + 1..]),
+                None => return Err(format!("expected `name=SGR`, found `{}`", entry)),
+            };
+            let color_spec = parse_sgr(sgr)?;
+
+            *match name {
+                "header-bug" => &mut self.header_bug,
+                "header-error" => &mut self.header_error,
+                "header-warning" => &mut self.header_warning,
+                "header-note" => &mut self.header_note,
+                "header-help" => &mut self.header_help,
+                "header-message" => &mut self.header_message,
+                "primary-label-bug" => &mut self.primary_label_bug,
+                "primary-label-error" => &mut self.primary_label_error,
+                "primary-label-warning" => &mut self.primary_label_warning,
+                "primary-label-note" => &mut self.primary_label_note,
+                "primary-label-help" => &mut self.primary_label_help,
+                "secondary-label" => &mut self.secondary_label,
+                "gutter" => &mut self.gutter,
+                "note" => &mut self.note,
+                "help" => &mut self.help,
+                "heading" => &mut self.heading,
+                "code" => &mut self.code,
+                "emphasis" => &mut self.emphasis,
+                _ => return Err(format!("unknown style `{}`", name)),
+            } = color_spec;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Styles {
+    fn default() -> Styles {
+        let header = |color| {
+            ColorSpec::new()
+                .set_bold(true)
+                .set_intense(true)
+                .set_fg(Some(color))
+                .clone()
+        };
+        let label = |color| ColorSpec::new().set_fg(Some(color)).clone();
+        // Blue is really difficult to see on the standard windows command line
+        let blue = if cfg!(windows) {
+            Color::Cyan
+        } else {
+            Color::Blue
+        };
+        let highlight = ColorSpec::new().set_bold(true).set_intense(true).clone();
+
+        Styles {
+            header_bug: header(Severity::Bug.color()),
+            header_error: header(Severity::Error.color()),
+            header_warning: header(Severity::Warning.color()),
+            header_note: header(Severity::Note.color()),
+            header_help: header(Severity::Help.color()),
+            header_message: highlight.clone(),
+
+            primary_label_bug: label(Severity::Bug.color()),
+            primary_label_error: label(Severity::Error.color()),
+            primary_label_warning: label(Severity::Warning.color()),
+            primary_label_note: label(Severity::Note.color()),
+            primary_label_help: label(Severity::Help.color()),
+            secondary_label: label(blue.clone()),
+
+            gutter: label(blue),
+            note: highlight.clone(),
+            help: highlight.clone(),
+
+            heading: highlight.clone().set_underline(true).clone(),
+            code: label(Color::Yellow),
+            emphasis: highlight,
+        }
+    }
+}
+
+impl FromStr for Styles {
+    type Err = String;
+
+    /// Parse a `GCC_COLORS`-like string, on top of the default styles
+    fn from_str(spec: &str) -> Result<Styles, String> {
+        let mut styles = Styles::default();
+        styles.apply(spec)?;
+        Ok(styles)
+    }
+}
+
+/// Parse a list of SGR parameters separated by semicolons
+fn parse_sgr(sgr: &str) -> Result<ColorSpec, String> {
+    fn basic(code: u8) -> Color {
+        match code {
+            0 => Color::Black,
+            1 => Color::Red,
+            2 => Color::Green,
+            3 => Color::Yellow,
+            4 => Color::Blue,
+            5 => Color::Magenta,
+            6 => Color::Cyan,
+            _ => Color::White,
+        }
+    }
+
+    let mut params = Vec::new();
+    for param in sgr.split(';').filter(|param| !param.is_empty()) {
+        match param.parse::<u8>() {
+            Ok(param) => params.push(param),
+            Err(_) => return Err(format!("invalid SGR parameter `{}`", param)),
+        }
+    }
+
+    let mut params = params.into_iter();
+    let mut spec = ColorSpec::new();
+    while let Some(param) = params.next() {
+        let mut next = |what: &str| {
+            params
+                .next()
+                .ok_or_else(|| format!("missing {} in `{}`", what, sgr))
+        };
+
+        match param {
+            0 => spec = ColorSpec::new(),
+            1 => {
+                spec.set_bold(true);
+            },
+            2 => {
+                spec.set_intense(false);
+            },
+            4 => {
+                spec.set_underline(true);
+            },
+            30..=37 => {
+                spec.set_fg(Some(basic(param - 30)));
+            },
+            39 => {
+                spec.set_fg(None);
+            },
+            40..=47 => {
+                spec.set_bg(Some(basic(param - 40)));
+            },
+            49 => {
+                spec.set_bg(None);
+            },
+            90..=97 => {
+                spec.set_fg(Some(basic(param - 90))).set_intense(true);
+            },
+            100..=107 => {
+                spec.set_bg(Some(basic(param - 100))).set_intense(true);
+            },
+            38 | 48 => {
+                let color = match next("color mode")? {
+                    5 => Color::Ansi256(next("color index")?),
+                    2 => Color::Rgb(next("red")?, next("green")?, next("blue")?),
+                    mode => return Err(format!("unsupported color mode `{}`", mode)),
+                };
+                if param == 38 {
+                    spec.set_fg(Some(color));
+                } else {
+                    spec.set_bg(Some(color));
+                }
+            },
+            _ => return Err(format!("unsupported SGR parameter `{}`", param)),
+        }
+    }
+
+    Ok(spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_styles() {
+        let styles =
+            "header-warning=01;35:primary-label-warning=38;5;208:gutter=90:help=4;48;2;1;2;3"
+                .parse::<Styles>()
+                .unwrap();
+
+        assert_eq!(
+            styles.header_warning,
+            ColorSpec::new()
+                .set_bold(true)
+                .set_fg(Some(Color::Magenta))
+                .clone(),
+        );
+        assert_eq!(
+            styles.primary_label_warning,
+            ColorSpec::new().set_fg(Some(Color::Ansi256(208))).clone(),
+        );
+        assert_eq!(
+            styles.gutter,
+            ColorSpec::new()
+                .set_fg(Some(Color::Black))
+                .set_intense(true)
+                .clone(),
+        );
+        assert_eq!(
+            styles.help,
+            ColorSpec::new()
+                .set_underline(true)
+                .set_bg(Some(Color::Rgb(1, 2, 3)))
+                .clone(),
+        );
+        // Unmentioned styles keep their defaults
+        assert_eq!(styles.header_error, Styles::default().header_error);
+    }
+
+    #[test]
+    fn parse_invalid_styles() {
+        assert_eq!(
+            "error=31".parse::<Styles>(),
+            Err("unknown style `error`".to_owned()),
+        );
+        assert_eq!(
+            "gutter".parse::<Styles>(),
+            Err("expected `name=SGR`, found `gutter`".to_owned()),
+        );
+        assert_eq!(
+            "gutter=31;x".parse::<Styles>(),
+            Err("invalid SGR parameter `x`".to_owned()),
+        );
+        assert_eq!(
+            "gutter=38;5".parse::<Styles>(),
+            Err("missing color index in `38;5`".to_owned()),
+        );
+    }
+}
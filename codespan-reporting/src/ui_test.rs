@@ -0,0 +1,443 @@
+//! Snapshot tests of the diagnostics reported for a directory of fixtures,
+//! in the style of the UI tests of `rustc`
+
+use codespan::{CodeMap, FileMap, FileName};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{env, fs, io};
+use termcolor::NoColor;
+
+use annotations::AnnotationChecker;
+use emitter::{emit, Config};
+use Diagnostic;
+
+/// Runs a compiler over each of the fixtures in a directory, and compares the
+/// diagnostics that it reports against the snapshot stored next to the
+/// fixture, with the extension `.stderr`.
+///
+/// The diagnostics are rendered with `emit`, without color. Paths within the
+/// directory are written as `$DIR`, so that the snapshots don't depend on
+/// where the tests are run, and spans like `12..15` are written as `$SPAN`,
+/// so that they don't depend on the other files in the codemap.
+///
+/// The diagnostics can also be checked against annotations in the comments
+/// of the fixtures, as described by `AnnotationChecker`.
+///
+/// When the `BLESS` environment variable is set to `1`, the snapshots are
+/// updated to match the diagnostics instead, and the snapshots of fixtures
+/// without any diagnostics are removed.
+///
+/// ```rust,no_run
+/// # extern crate codespan;
+/// # extern crate codespan_reporting;
+/// use codespan::{ByteOffset, Span};
+/// use codespan_reporting::{Diagnostic, Label, UiTests};
+///
+/// # fn main() {
+/// UiTests::new("tests/ui", "lang").run(|_codemap, file| {
+///     match file.src().find("undefined") {
+///         Some(offset) => {
+///             let start = file.span().start() + ByteOffset(offset as i64);
+///             let span = Span::from_offset(start, ByteOffset(9));
+///             let label = Label::new_primary(span);
+///             vec![Diagnostic::new_error("undefined variable").with_label(label)]
+///         },
+///         None => Vec::new(),
+///     }
+/// });
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct UiTests {
+    /// The directory containing the fixtures, which is searched recursively
+    pub dir: PathBuf,
+    /// The extension of the fixtures, without the leading `.`
+    pub extension: String,
+    /// The configuration used to render the diagnostics
+    pub config: Config,
+    /// Whether the snapshots are updated, rather than checked
+    pub bless: bool,
+    /// Additional replacements made in the rendered diagnostics, in order,
+    /// after the paths and spans have been normalized
+    pub replacements: Vec<(String, String)>,
+    /// Checks the diagnostics against the annotations in the fixtures,
+    /// before comparing them with the snapshots
+    pub annotations: Option<AnnotationChecker>,
+}
+
+impl UiTests {
+    /// Snapshot tests for the fixtures in `dir`, which are blessed if the
+    /// `BLESS` environment variable is set to `1`
+    pub fn new<P: Into<PathBuf>, S: Into<String>>(dir: P, extension: S) -> UiTests {
+        UiTests {
+            dir: dir.into(),
+            extension: extension.into(),
+            config: Config::default(),
+            bless: env::var_os("BLESS").is_some_and(|bless| bless == "1"),
+            replacements: Vec::new(),
+            annotations: None,
+        }
+    }
+
+    pub fn with_config(mut self, config: Config) -> UiTests {
+        self.config = config;
+        self
+    }
+
+    pub fn with_bless(mut self, bless: bool) -> UiTests {
+        self.bless = bless;
+        self
+    }
+
+    pub fn with_replacement<S, T>(mut self, from: S, to: T) -> UiTests
+    where
+        S: Into<String>,
+        T: Into<String>,
+    {
+        self.replacements.push((from.into(), to.into()));
+        self
+    }
+
+    pub fn with_annotations(mut self, annotations: AnnotationChecker) -> UiTests {
+        self.annotations = Some(annotations);
+        self
+    }
+
+    /// Check the snapshots of every fixture, panicking with a report of the
+    /// differences if any of them fail
+    pub fn run<F>(&self, compile: F)
+    where
+        F: FnMut(&mut CodeMap, &Arc<FileMap>) -> Vec<Diagnostic>,
+    {
+        if let Err(report) = self.check(compile) {
+            panic!("{}", report);
+        }
+    }
+
+    /// Check the snapshots of every fixture, returning a report of the
+    /// differences if any of them fail.
+    ///
+    /// Each fixture is added to a new codemap, which is passed to `compile`
+    /// along with the fixture.
+    pub fn check<F>(&self, mut compile: F) -> Result<(), String>
+    where
+        F: FnMut(&mut CodeMap, &Arc<FileMap>) -> Vec<Diagnostic>,
+    {
+        let mut fixtures = Vec::new();
+        find_fixtures(&self.dir, &self.extension, &mut fixtures)
+            .map_err(|error| format!("could not read `{}`: {}", self.dir.display(), error))?;
+        fixtures.sort();
+
+        let mut report = String::new();
+        let mut failures = 0;
+        let mut mismatches = 0;
+        for fixture in &fixtures {
+            let failure = match self.check_fixture(fixture, &mut compile) {
+                Ok(()) => continue,
+                Err(Failure::Error(failure)) => failure,
+                Err(Failure::Mismatch(failure)) => {
+                    mismatches += 1;
+                    failure
+                },
+            };
+            writeln!(report, "{}", failure).unwrap();
+            failures += 1;
+        }
+
+        match failures {
+            0 => Ok(()),
+            _ => {
+                write!(report, "{} of {} UI tests failed", failures, fixtures.len()).unwrap();
+                // Blessing only fixes snapshots that differ from the output
+                if mismatches > 0 {
+                    write!(report, "; run with `BLESS=1` to update the snapshots").unwrap();
+                }
+                Err(report)
+            },
+        }
+    }
+
+    fn check_fixture<F>(&self, fixture: &Path, compile: &mut F) -> Result<(), Failure>
+    where
+        F: FnMut(&mut CodeMap, &Arc<FileMap>) -> Vec<Diagnostic>,
+    {
+        let read_error = |error: io::Error| {
+            Failure::Error(format!("could not read `{}`: {}", fixture.display(), error))
+        };
+
+        let src = fs::read_to_string(fixture).map_err(read_error)?;
+        let mut codemap = CodeMap::new();
+        let file = codemap.add_filemap(FileName::real(fixture), src);
+
+        let diagnostics = compile(&mut codemap, &file);
+        if let Some(ref annotations) = self.annotations {
+            let failures = annotations.check(&file, &diagnostics);
+            if !failures.is_empty() {
+                return Err(Failure::Error(format!(
+                    "the diagnostics for `{}` do not match its annotations:\n{}",
+                    fixture.display(),
+                    self.normalize(&self.render(&codemap, &failures)),
+                )));
+            }
+        }
+
+        let actual = self.normalize(&self.render(&codemap, &diagnostics));
+
+        let snapshot = fixture.with_extension("stderr");
+        let expected = match fs::read_to_string(&snapshot) {
+            Ok(expected) => expected,
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(read_error(error)),
+        };
+        if actual == expected {
+            return Ok(());
+        }
+
+        if self.bless {
+            let result = match actual.as_str() {
+                "" => fs::remove_file(&snapshot),
+                _ => fs::write(&snapshot, &actual),
+            };
+            return result.map_err(|error| {
+                Failure::Error(format!("could not write `{}`: {}", snapshot.display(), error))
+            });
+        }
+
+        Err(Failure::Mismatch(format!(
+            "the diagnostics for `{}` differ from `{}`:\n{}",
+            fixture.display(),
+            snapshot.display(),
+            diff(&expected, &actual),
+        )))
+    }
+
+    fn render(&self, codemap: &CodeMap, diagnostics: &[Diagnostic]) -> String {
+        let mut rendered = NoColor::new(Vec::new());
+        for diagnostic in diagnostics {
+            emit(&mut rendered, &self.config, codemap, diagnostic).unwrap();
+        }
+        String::from_utf8_lossy(&rendered.into_inner()).into_owned()
+    }
+
+    /// Normalize the paths, spans and other replacements in some rendered
+    /// diagnostics
+    pub fn normalize(&self, rendered: &str) -> String {
+        let dir = self.dir.display().to_string();
+        let mut normalized = normalize_spans(&rendered.replace(&dir, "$DIR"));
+        if cfg!(windows) {
+            normalized = normalized.replace(&dir.replace('\\', "/"), "$DIR");
+        }
+
+        for (from, to) in &self.replacements {
+            normalized = normalized.replace(from, to);
+        }
+        normalized
+    }
+}
+
+/// The report of a fixture that failed
+enum Failure {
+    /// A failure that updating the snapshot would not fix
+    Error(String),
+    /// The diagnostics differ from the snapshot
+    Mismatch(String),
+}
+
+fn find_fixtures(dir: &Path, extension: &str, fixtures: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_fixtures(&path, extension, fixtures)?;
+        } else if path.extension().is_some_and(|ext| ext == extension) {
+            fixtures.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Replace spans like `12..15` with `$SPAN`
+fn normalize_spans(rendered: &str) -> String {
+    let mut normalized = String::with_capacity(rendered.len());
+    let mut rest = rendered;
+
+    while let Some(start) = rest.find("..") {
+        let before = &rest[..start];
+        let after = &rest[start + 2..];
+        let digits_before = before.len()
+            - before
+                .trim_end_matches(|ch: char| ch.is_ascii_digit())
+                .len();
+        let digits_after = after.len()
+            - after
+                .trim_start_matches(|ch: char| ch.is_ascii_digit())
+                .len();
+
+        if digits_before > 0 && digits_after > 0 {
+            normalized.push_str(&before[..before.len() - digits_before]);
+            normalized.push_str("$SPAN");
+            rest = &after[digits_after..];
+        } else {
+            normalized.push_str(&rest[..start + 2]);
+            rest = after;
+        }
+    }
+
+    normalized.push_str(rest);
+    normalized
+}
+
+/// A line-by-line diff between two snapshots, with removed lines marked
+/// with `-` and added lines marked with `+`
+fn diff(expected: &str, actual: &str) -> String {
+    let expected = expected.lines().collect::<Vec<_>>();
+    let actual = actual.lines().collect::<Vec<_>>();
+
+    // The length of the longest common subsequence of the lines after each
+    // pair of positions
+    let mut lengths = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lengths[i][j] = if expected[i] == actual[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                usize::max(lengths[i + 1][j], lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            writeln!(diff, " {}", expected[i]).unwrap();
+            i += 1;
+            j += 1;
+        } else if i < expected.len()
+            && (j == actual.len() || lengths[i + 1][j] >= lengths[i][j + 1])
+        {
+            writeln!(diff, "-{}", expected[i]).unwrap();
+            i += 1;
+        } else {
+            writeln!(diff, "+{}", actual[j]).unwrap();
+            j += 1;
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use codespan::{ByteOffset, Span};
+
+    use super::*;
+    use Label;
+
+    /// Reports an error for each occurrence of `undefined`
+    fn compile(_: &mut CodeMap, file: &Arc<FileMap>) -> Vec<Diagnostic> {
+        file.src()
+            .match_indices("undefined")
+            .map(|(offset, word)| {
+                let start = file.span().start() + ByteOffset(offset as i64);
+                let span = Span::from_offset(start, ByteOffset(word.len() as i64));
+                Diagnostic::new_error(format!("undefined variable at {}", span))
+                    .with_label(Label::new_primary(span))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bless_and_check() {
+        let dir = env::temp_dir().join(format!("codespan-ui-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("ok.lang"), "defined\n").unwrap();
+        fs::write(dir.join("nested/bad.lang"), "x\nundefined\n").unwrap();
+        fs::write(dir.join("stale.lang"), "defined\n").unwrap();
+        fs::write(dir.join("stale.stderr"), "error: old\n").unwrap();
+
+        let tests = UiTests::new(&dir, "lang").with_bless(false);
+        let report = tests.check(compile).unwrap_err();
+        assert!(
+            report.ends_with("2 of 3 UI tests failed; run with `BLESS=1` to update the snapshots")
+        );
+
+        tests.clone().with_bless(true).check(compile).unwrap();
+        assert!(!dir.join("stale.stderr").exists());
+        assert_eq!(
+            fs::read_to_string(dir.join("nested/bad.stderr")).unwrap(),
+            "\
+error: undefined variable at $SPAN
+- $DIR/nested/bad.lang:2:1
+2 | undefined
+  | ^^^^^^^^^
+",
+        );
+        tests.check(compile).unwrap();
+
+        fs::write(dir.join("ok.lang"), "undefined\n").unwrap();
+        let report = tests
+            .with_replacement("undefined variable", "unknown name")
+            .check(compile)
+            .unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(report.contains(&format!(
+            "the diagnostics for `{}` differ from `{}`:\n\
+             -error: undefined variable at $SPAN\n\
+             +error: unknown name at $SPAN\n \
+             - $DIR/nested/bad.lang:2:1\n",
+            dir.join("nested/bad.lang").display(),
+            dir.join("nested/bad.stderr").display(),
+        )));
+        assert!(report.contains(&format!(
+            "the diagnostics for `{}` differ from `{}`:\n\
+             +error: unknown name at $SPAN\n\
+             +- $DIR/ok.lang:1:1\n",
+            dir.join("ok.lang").display(),
+            dir.join("ok.stderr").display(),
+        )));
+        assert!(
+            report.ends_with("2 of 3 UI tests failed; run with `BLESS=1` to update the snapshots")
+        );
+    }
+
+    #[test]
+    fn annotations() {
+        let dir = env::temp_dir().join(format!("codespan-ui-annotations-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("a.lang"),
+            "undefined //~ ERROR variable\n",
+        )
+        .unwrap();
+        fs::write(dir.join("b.lang"), "undefined\n").unwrap();
+
+        let report = UiTests::new(&dir, "lang")
+            .with_bless(true)
+            .with_annotations(AnnotationChecker::default())
+            .check(compile)
+            .unwrap_err();
+        let snapshot_exists = dir.join("a.stderr").exists();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(snapshot_exists);
+        // Annotation failures are normalized like snapshots, but can't be
+        // fixed by blessing
+        assert!(report.starts_with(&format!(
+            "the diagnostics for `{}` do not match its annotations:\n\
+             error: unexpected error: undefined variable at $SPAN\n\
+             - $DIR/b.lang:1:1\n",
+            dir.join("b.lang").display(),
+        )));
+        assert!(report.ends_with("1 of 2 UI tests failed"));
+    }
+
+    #[test]
+    fn normalize_spans_and_diff() {
+        assert_eq!(
+            normalize_spans("at 1..23, 1.., ..2, 4...5 and 6..7"),
+            "at $SPAN, 1.., ..2, 4...5 and $SPAN",
+        );
+        assert_eq!(diff("a\nb\nc\n", "a\nc\nd\n"), " a\n-b\n c\n+d\n");
+    }
+}
@@ -0,0 +1,82 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const SOURCE: &str = "let x = y;\n";
+
+/// The first file in a codemap starts at index 1, so `y` is at `9..10`
+const DIAGNOSTICS: &str = r#"
+{"severity":"Error","code":"E0425","message":"cannot find value `y`","labels":[{"span":{"start":9,"end":10},"message":"not found","style":"Primary"}]}
+{"severity":"Warning","code":null,"message":"unused variable `x`","notes":[{"severity":"Help","message":"prefix it with an underscore"}]}
+"#;
+
+/// Run `codespan-render` on a copy of `SOURCE` named after the test
+fn render(test: &str, args: &[&str], diagnostics: &str) -> Output {
+    let name = format!("codespan-render-{}-{}.lang", test, std::process::id());
+    let path = env::temp_dir().join(name);
+    fs::write(&path, SOURCE).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_codespan-render"))
+        .args(["--color", "never"])
+        .args(args)
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(diagnostics.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    fs::remove_file(&path).unwrap();
+    output
+}
+
+#[test]
+fn rich() {
+    let output = render("rich", &[], DIAGNOSTICS);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(output.status.success());
+    assert!(stdout.starts_with("error[E0425]: cannot find value `y`\n- "));
+    assert!(stdout.ends_with(
+        "\
+1 | let x = y;
+  |         ^ not found
+warning: unused variable `x`
+ = help: prefix it with an underscore
+",
+    ));
+}
+
+#[test]
+fn formats() {
+    let output = render("short", &["--format", "short"], DIAGNOSTICS);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(".lang:1:9: error[E0425]: cannot find value `y`: not found\n"));
+
+    let output = render(
+        "sarif",
+        &["--format", "sarif", "--tool", "lang"],
+        DIAGNOSTICS,
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\"name\": \"lang\""));
+    assert!(stdout.contains("\"ruleId\": \"E0425\""));
+}
+
+#[test]
+fn invalid_input() {
+    let output = render("invalid", &[], "{\"severity\":\"Error\"}\n");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("codespan-render: invalid diagnostic on line 1: missing field `message`"));
+}
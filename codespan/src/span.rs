@@ -4,7 +4,7 @@ use std::{cmp, fmt};
 use index::{ByteIndex, Index};
 
 /// A region of code in a source file
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Ord, PartialOrd)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Ord, PartialOrd)]
 #[cfg_attr(feature = "serialization", derive(Deserialize, Serialize))]
 pub struct Span<I> {
     start: I,